@@ -1,6 +1,13 @@
 use quote::{format_ident, quote};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, env, fs, path::Path};
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::Path,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 /// Configuration for URL cleaning rules
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,58 +36,91 @@ pub struct Provider {
     pub force_redirection: bool,
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("cargo:rerun-if-changed=Rules/data.min.json");
+#[allow(dead_code)]
+#[path = "src/js_regex.rs"]
+mod js_regex;
 
-    let json = fs::read_to_string("./Rules/data.min.json")?;
-    let url_config: ClearUrlsConfig = serde_json::from_str(&json)?;
+use js_regex::Usage;
 
-    // Track unique regex patterns for deduplication
-    let mut regex_counter = 0;
-    let mut regex_map: HashMap<String, proc_macro2::Ident> = HashMap::new();
+/// A pattern the build script had to leave out of the embedded rules
+struct Skipped {
+    provider: String,
+    field: &'static str,
+    pattern: String,
+    reason: String,
+}
 
-    let mut get_regex_name = |pattern: &str| -> proc_macro2::Ident {
-        if let Some(name) = regex_map.get(pattern) {
-            return name.clone();
-        }
-        let name = format_ident!("RE_{}", regex_counter.to_string());
-        regex_counter += 1;
-        regex_map.insert(pattern.to_string(), name.clone());
-        name
-    };
+/// Translate a pattern from JavaScript syntax and check that the runtime will be able to compile it
+fn check_pattern(pattern: &str, usage: Usage, backtracking: bool) -> Result<(), String> {
+    let translated = js_regex::translate(pattern, usage);
+
+    match translated.unsupported {
+        // Leave it to the backtracking engine at runtime
+        Some(_) if backtracking => Ok(()),
+        Some(reason) => Err(format!("{} needs the `backtracking` feature", reason)),
+        None => match regex::Regex::new(&translated.pattern) {
+            Ok(_) => Ok(()),
+            Err(_) if backtracking => Ok(()),
+            Err(e) => Err(e
+                .to_string()
+                .lines()
+                .last()
+                .unwrap_or_default()
+                .trim()
+                .to_string()),
+        },
+    }
+}
 
-    // Collect all unique regex patterns
-    for provider in url_config.providers.values() {
-        get_regex_name(&provider.url_pattern);
-        for rule in &provider.rules {
-            get_regex_name(rule);
-        }
-        for rule in &provider.raw_rules {
-            get_regex_name(rule);
-        }
-        for exc in &provider.exceptions {
-            get_regex_name(exc);
-        }
-        for redir in &provider.redirections {
-            get_regex_name(redir);
-        }
-        for ref_mark in &provider.referral_marketing {
-            get_regex_name(ref_mark);
-        }
+/// The commit the rules submodule is checked out at, when building from a git checkout
+fn submodule_commit() -> Option<String> {
+    // Without its own `.git`, git would report the commit of the enclosing repository instead
+    if !Path::new("Rules/.git").exists() {
+        return None;
     }
 
-    // Generate all regex statics using LazyLock
-    let mut regex_defs = Vec::new();
-    for (pattern, name) in &regex_map {
-        regex_defs.push(quote! {
-            static #name: std::sync::LazyLock<regex::Regex> =
-                std::sync::LazyLock::new(|| regex::Regex::new(#pattern).unwrap());
-        });
+    let output = Command::new("git")
+        .args(["-C", "Rules", "rev-parse", "HEAD"])
+        .output()
+        .ok()?;
+
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .filter(|commit| !commit.is_empty())
+}
+
+/// Build time in seconds since the Unix epoch, honouring `SOURCE_DATE_EPOCH` for reproducible builds
+fn build_timestamp() -> u64 {
+    env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default()
+        })
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    println!("cargo:rerun-if-changed=Rules/data.min.json");
+    println!("cargo:rerun-if-changed=src/js_regex.rs");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    if Path::new(".git/modules/Rules/HEAD").exists() {
+        println!("cargo:rerun-if-changed=.git/modules/Rules/HEAD");
     }
 
+    let json = fs::read_to_string("./Rules/data.min.json")?;
+    let url_config: ClearUrlsConfig = serde_json::from_str(&json)?;
+    let backtracking = env::var_os("CARGO_FEATURE_BACKTRACKING").is_some();
+
     // Generate all static arrays first
     let mut all_static_defs = Vec::new();
     let mut provider_entries = Vec::new();
+    let mut skipped = Vec::new();
+    let mut rule_count = 0;
 
     for (provider_name, provider) in &url_config.providers {
         // Create a valid Rust identifier from the provider name
@@ -89,11 +129,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .to_uppercase();
 
         // Rust identifiers can't start with a number, so prefix with P_
-        if safe_name
-            .chars()
-            .next()
-            .map_or(false, |c| c.is_ascii_digit())
-        {
+        if safe_name.chars().next().is_some_and(|c| c.is_ascii_digit()) {
             safe_name = format!("P_{}", safe_name);
         }
 
@@ -103,153 +139,187 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .filter(|c| c.is_alphanumeric() || *c == '_')
             .collect();
 
-        let url_pattern_regex = regex_map.get(&provider.url_pattern).unwrap();
-
-        // Generate rules array
-        let rules_array_name = format_ident!("{}_RULES", safe_name);
-        let rules: Vec<_> = provider
-            .rules
-            .iter()
-            .map(|r| regex_map.get(r).unwrap())
-            .collect();
-
-        let rules_def = if !rules.is_empty() {
-            quote! {
-                static #rules_array_name: std::sync::LazyLock<Vec<&'static regex::Regex>> =
-                    std::sync::LazyLock::new(|| vec![#(&#rules),*]);
-            }
-        } else {
-            quote! {
-                static #rules_array_name: std::sync::LazyLock<Vec<&'static regex::Regex>> =
-                    std::sync::LazyLock::new(|| vec![]);
-            }
-        };
-
-        // Generate raw_rules array
-        let raw_rules_array_name = format_ident!("{}_RAW_RULES", safe_name);
-        let raw_rules: Vec<_> = provider
-            .raw_rules
-            .iter()
-            .map(|r| regex_map.get(r).unwrap())
-            .collect();
+        // Without its urlPattern the provider can't apply to anything
+        let url_pattern = &provider.url_pattern;
+        if let Err(reason) = check_pattern(url_pattern, Usage::Url, backtracking) {
+            skipped.push(Skipped {
+                provider: provider_name.clone(),
+                field: "urlPattern",
+                pattern: url_pattern.clone(),
+                reason,
+            });
+            continue;
+        }
 
-        let raw_rules_def = if !raw_rules.is_empty() {
-            quote! {
-                static #raw_rules_array_name: std::sync::LazyLock<Vec<&'static regex::Regex>> =
-                    std::sync::LazyLock::new(|| vec![#(&#raw_rules),*]);
-            }
-        } else {
-            quote! {
-                static #raw_rules_array_name: std::sync::LazyLock<Vec<&'static regex::Regex>> =
-                    std::sync::LazyLock::new(|| vec![]);
-            }
+        // Keep only the patterns the runtime can compile, reporting the rest
+        let mut checked = |field: &'static str, patterns: &[String], usage: Usage| {
+            patterns
+                .iter()
+                .filter(
+                    |pattern| match check_pattern(pattern, usage, backtracking) {
+                        Ok(()) => true,
+                        Err(reason) => {
+                            skipped.push(Skipped {
+                                provider: provider_name.clone(),
+                                field,
+                                pattern: pattern.to_string(),
+                                reason,
+                            });
+                            false
+                        }
+                    },
+                )
+                .cloned()
+                .collect::<Vec<_>>()
         };
 
-        // Generate exceptions array
-        let exceptions_array_name = format_ident!("{}_EXCEPTIONS", safe_name);
-        let exceptions: Vec<_> = provider
-            .exceptions
-            .iter()
-            .map(|e| regex_map.get(e).unwrap())
-            .collect();
-
-        let exceptions_def = if !exceptions.is_empty() {
-            quote! {
-                static #exceptions_array_name: std::sync::LazyLock<Vec<&'static regex::Regex>> =
-                    std::sync::LazyLock::new(|| vec![#(&#exceptions),*]);
-            }
-        } else {
-            quote! {
-                static #exceptions_array_name: std::sync::LazyLock<Vec<&'static regex::Regex>> =
-                    std::sync::LazyLock::new(|| vec![]);
-            }
-        };
+        let fields = [
+            ("RULES", checked("rules", &provider.rules, Usage::Key)),
+            (
+                "RAW_RULES",
+                checked("rawRules", &provider.raw_rules, Usage::Replace),
+            ),
+            (
+                "EXCEPTIONS",
+                checked("exceptions", &provider.exceptions, Usage::Url),
+            ),
+            (
+                "REDIRECTIONS",
+                checked("redirections", &provider.redirections, Usage::Url),
+            ),
+            (
+                "REFERRAL",
+                checked(
+                    "referralMarketing",
+                    &provider.referral_marketing,
+                    Usage::Key,
+                ),
+            ),
+        ];
 
-        // Generate redirections array
-        let redirections_array_name = format_ident!("{}_REDIRECTIONS", safe_name);
-        let redirections: Vec<_> = provider
-            .redirections
+        rule_count += fields
             .iter()
-            .map(|r| regex_map.get(r).unwrap())
-            .collect();
+            .map(|(_, patterns)| patterns.len())
+            .sum::<usize>();
 
-        let redirections_def = if !redirections.is_empty() {
-            quote! {
-                static #redirections_array_name: std::sync::LazyLock<Vec<&'static regex::Regex>> =
-                    std::sync::LazyLock::new(|| vec![#(&#redirections),*]);
-            }
-        } else {
-            quote! {
-                static #redirections_array_name: std::sync::LazyLock<Vec<&'static regex::Regex>> =
-                    std::sync::LazyLock::new(|| vec![]);
-            }
-        };
-
-        // Generate referral_marketing array
-        let referral_array_name = format_ident!("{}_REFERRAL", safe_name);
-        let referral_marketing: Vec<_> = provider
-            .referral_marketing
+        // Generate one array per field
+        let array_names: Vec<_> = fields
             .iter()
-            .map(|r| regex_map.get(r).unwrap())
+            .map(|(suffix, patterns)| {
+                let array_name = format_ident!("{}_{}", safe_name, suffix);
+                all_static_defs.push(quote! {
+                    static #array_name: &[&str] = &[#(#patterns),*];
+                });
+                array_name
+            })
             .collect();
 
-        let referral_def = if !referral_marketing.is_empty() {
-            quote! {
-                static #referral_array_name: std::sync::LazyLock<Vec<&'static regex::Regex>> =
-                    std::sync::LazyLock::new(|| vec![#(&#referral_marketing),*]);
-            }
-        } else {
-            quote! {
-                static #referral_array_name: std::sync::LazyLock<Vec<&'static regex::Regex>> =
-                    std::sync::LazyLock::new(|| vec![]);
-            }
+        let [
+            rules,
+            raw_rules,
+            exceptions,
+            redirections,
+            referral_marketing,
+        ] = &array_names[..]
+        else {
+            unreachable!("There are five pattern fields per provider");
         };
 
         let complete_provider = provider.complete_provider;
         let force_redirection = provider.force_redirection;
 
-        // Add all static definitions
-        all_static_defs.push(rules_def);
-        all_static_defs.push(raw_rules_def);
-        all_static_defs.push(exceptions_def);
-        all_static_defs.push(redirections_def);
-        all_static_defs.push(referral_def);
-
         provider_entries.push(quote! {
             Provider {
                 name: #provider_name,
-                url_pattern: &#url_pattern_regex,
-                rules: &#rules_array_name,
-                raw_rules: &#raw_rules_array_name,
-                exceptions: &#exceptions_array_name,
-                redirections: &#redirections_array_name,
-                referral_marketing: &#referral_array_name,
+                url_pattern: #url_pattern,
+                rules: #rules,
+                raw_rules: #raw_rules,
+                exceptions: #exceptions,
+                redirections: #redirections,
+                referral_marketing: #referral_marketing,
                 complete_provider: #complete_provider,
                 force_redirection: #force_redirection,
             }
         });
     }
 
+    for skip in &skipped {
+        println!(
+            "cargo:warning=Skipping {} pattern `{}` of provider {}: {}",
+            skip.field, skip.pattern, skip.provider, skip.reason
+        );
+    }
+
+    let skipped_entries = skipped.iter().map(|skip| {
+        let Skipped {
+            provider,
+            field,
+            pattern,
+            reason,
+        } = skip;
+        quote! {
+            Skipped {
+                provider: #provider,
+                field: #field,
+                pattern: #pattern,
+                reason: #reason,
+            }
+        }
+    });
+
+    let sha256: String = Sha256::digest(json.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    let commit = match submodule_commit() {
+        Some(commit) => quote! { Some(#commit) },
+        None => quote! { None },
+    };
+    let provider_count = provider_entries.len();
+    let built_at = build_timestamp();
+
     // Generate the complete file
     let output = quote! {
-        use regex::Regex;
-        use std::sync::LazyLock;
-
+        /// A provider as embedded at build time, its patterns are compiled when the ruleset is first used
         #[derive(Debug)]
         pub struct Provider {
             pub name: &'static str,
-            pub url_pattern: &'static LazyLock<Regex>,
-            pub rules: &'static LazyLock<Vec<&'static Regex>>,
-            pub raw_rules: &'static LazyLock<Vec<&'static Regex>>,
-            pub exceptions: &'static LazyLock<Vec<&'static Regex>>,
-            pub redirections: &'static LazyLock<Vec<&'static Regex>>,
-            pub referral_marketing: &'static LazyLock<Vec<&'static Regex>>,
+            pub url_pattern: &'static str,
+            pub rules: &'static [&'static str],
+            pub raw_rules: &'static [&'static str],
+            pub exceptions: &'static [&'static str],
+            pub redirections: &'static [&'static str],
+            pub referral_marketing: &'static [&'static str],
             pub complete_provider: bool,
             pub force_redirection: bool,
         }
 
-        // Generate all regex statics
-        #(#regex_defs)*
+        /// A pattern left out at build time because it can't be compiled
+        #[derive(Debug)]
+        pub struct Skipped {
+            pub provider: &'static str,
+            pub field: &'static str,
+            pub pattern: &'static str,
+            pub reason: &'static str,
+        }
+
+        /// Which ClearURLs data was embedded, and when
+        #[derive(Debug)]
+        pub struct BuildInfo {
+            pub sha256: &'static str,
+            pub commit: Option<&'static str>,
+            pub providers: usize,
+            pub rules: usize,
+            pub built_at: u64,
+        }
+
+        pub static BUILD_INFO: BuildInfo = BuildInfo {
+            sha256: #sha256,
+            commit: #commit,
+            providers: #provider_count,
+            rules: #rule_count,
+            built_at: #built_at,
+        };
 
         // Generate all rule array statics
         #(#all_static_defs)*
@@ -258,6 +328,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         pub static PROVIDERS: &[Provider] = &[
             #(#provider_entries),*
         ];
+
+        pub static SKIPPED: &[Skipped] = &[
+            #(#skipped_entries),*
+        ];
     };
 
     let out_dir = env::var("OUT_DIR")?;
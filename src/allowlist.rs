@@ -0,0 +1,131 @@
+//! The parameters strict mode keeps, as Neat URL rules (`v@*.youtube.com`, `q@google.*`, `page`).
+//!
+//! The built-in allowlists cover the functional parameters of popular sites: video and playlist IDs, search
+//! queries and the item IDs of shops that keep them in the query string. Users add their own entries through
+//! `CleaningOptions::allowed_params`.
+
+use anyhow::{Result, bail};
+
+use crate::neat_url::NeatRule;
+
+/// Functional parameters of popular sites, by Neat URL domain
+const BUILTIN: &[(&str, &[&str])] = &[
+    // Video
+    (
+        "*.youtube.com",
+        &["v", "t", "list", "index", "search_query", "start", "end"],
+    ),
+    ("youtu.be", &["t", "list", "index"]),
+    ("*.youtube-nocookie.com", &["start", "end", "list"]),
+    ("*.vimeo.com", &["h"]),
+    ("*.twitch.tv", &["t", "video"]),
+    // Search engines
+    (
+        "*.google.*",
+        &["q", "tbm", "start", "udm", "hl", "cid", "id"],
+    ),
+    ("*.bing.com", &["q", "first"]),
+    ("duckduckgo.com", &["q", "ia", "iax"]),
+    ("search.yahoo.com", &["p", "b"]),
+    ("*.yandex.*", &["text", "p"]),
+    ("search.brave.com", &["q", "source"]),
+    ("*.startpage.com", &["query", "page"]),
+    ("*.ecosia.org", &["q", "p"]),
+    ("kagi.com", &["q"]),
+    ("*.baidu.com", &["wd", "pn"]),
+    // Shops
+    ("*.amazon.*", &["k", "i", "node", "page"]),
+    ("*.ebay.*", &["_nkw", "_pgn", "_sacat"]),
+    ("*.aliexpress.*", &["SearchText", "page"]),
+    ("*.etsy.com", &["q", "page"]),
+    ("*.walmart.com", &["q", "page"]),
+    ("*.bestbuy.com", &["skuId", "st"]),
+    ("*.taobao.com", &["id", "q"]),
+    ("*.tmall.com", &["id", "q"]),
+    ("item.jd.com", &["id"]),
+    // Everything else
+    (
+        "*.wikipedia.org",
+        &["title", "search", "oldid", "diff", "curid", "action"],
+    ),
+    ("github.com", &["q", "tab", "page", "type", "path"]),
+    ("stackoverflow.com", &["q", "tab", "page"]),
+    ("news.ycombinator.com", &["id", "p", "q"]),
+    ("*.reddit.com", &["q", "sort", "t", "context"]),
+    ("*.facebook.com", &["story_fbid", "id", "v", "fbid"]),
+    ("x.com", &["q", "f"]),
+    ("twitter.com", &["q", "f"]),
+];
+
+/// An allowlist entry and where it came from
+#[derive(Debug, Clone)]
+pub(crate) struct Entry {
+    pub(crate) rule: NeatRule,
+    /// `built-in` or `options`
+    pub(crate) source: &'static str,
+}
+
+/// The built-in allowlists, plus the user's entries
+#[derive(Debug, Clone)]
+pub(crate) struct Allowlist {
+    entries: Vec<Entry>,
+}
+
+impl Allowlist {
+    /// Parse the user's entries on top of the built-in ones. Entries can only name parameters.
+    pub(crate) fn new(allowed_params: &[String]) -> Result<Self> {
+        let mut entries = Vec::new();
+
+        for (domain, params) in BUILTIN {
+            for param in *params {
+                entries.push(Entry {
+                    rule: NeatRule::parse(&format!("{}@{}", param, domain))?,
+                    source: "built-in",
+                });
+            }
+        }
+
+        for allowed in allowed_params {
+            let rule = NeatRule::parse(allowed)?;
+            if rule.exclude || !rule.is_param() {
+                bail!("Allowed parameter '{}' has to name parameters", allowed);
+            }
+            entries.push(Entry {
+                rule,
+                source: "options",
+            });
+        }
+
+        Ok(Self { entries })
+    }
+
+    /// The entry keeping a parameter on this host, if there is one
+    pub(crate) fn find(&self, host: &str, key: &str) -> Option<&Entry> {
+        self.entries
+            .iter()
+            .find(|entry| entry.rule.applies_to(host) && entry.rule.matches_param(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allowlist() {
+        let allowlist = Allowlist::new(&["session*@app.test".to_string()]).unwrap();
+
+        let entry = allowlist.find("www.youtube.com", "v").unwrap();
+        assert_eq!(entry.rule.source, "v@*.youtube.com");
+        assert_eq!(entry.source, "built-in");
+        assert!(allowlist.find("www.google.co.uk", "q").is_some());
+        assert!(allowlist.find("www.youtube.com", "feature").is_none());
+        assert_eq!(
+            allowlist.find("app.test", "session_id").unwrap().source,
+            "options"
+        );
+
+        assert!(Allowlist::new(&["$$".to_string()]).is_err());
+        assert!(Allowlist::new(&["!v@youtube.com".to_string()]).is_err());
+    }
+}
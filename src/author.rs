@@ -0,0 +1,253 @@
+//! Writing a provider from an example URL: which parameters to strip, which one holds a redirect target and
+//! which paths to leave alone, turned into an escaped ClearURLs entry along with test cases for it.
+
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{
+    CleaningOptions, ProviderSpec, RuleFile, RuleSet, RuleSource, UrlCleaner,
+    cases::{Case, CaseFile, CaseResult, Expectation},
+    mine::registrable_domain,
+    set_query_pairs,
+};
+
+/// What the provider should do to URLs like the example
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Draft {
+    /// Defaults to the domain without its top level domain, like `example` for `shop.example.com`
+    pub name: Option<String>,
+    /// Parameters to remove
+    pub strip: Vec<String>,
+    /// The parameter holding the URL to redirect to
+    pub redirect: Option<String>,
+    /// Path prefixes, like `/checkout`, where the provider shouldn't do anything
+    pub exceptions: Vec<String>,
+}
+
+/// The generated provider, its test cases and how they did against the ruleset with the provider added
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Authored {
+    pub name: String,
+    pub file: RuleFile,
+    pub cases: CaseFile,
+    pub results: Vec<CaseResult>,
+}
+
+/// The decoded query parameters of a URL, to choose from
+pub fn params(url: &str) -> Result<Vec<(String, String)>> {
+    let url = Url::parse(url).context("Failed to parse URL")?;
+    Ok(url.query_pairs().into_owned().collect())
+}
+
+/// Write a provider for the example URL and validate it by running its cases through `UrlCleaner`, on top of
+/// `rules`
+pub fn author(example: &str, draft: &Draft, rules: &RuleSet) -> Result<Authored> {
+    let url = Url::parse(example).context("Failed to parse URL")?;
+    let host = url.host_str().context("The URL has no host")?.to_string();
+    let domain = registrable_domain(&host);
+
+    let name = match &draft.name {
+        Some(name) => name.clone(),
+        None => domain.split('.').next().unwrap_or(&domain).to_string(),
+    };
+    if rules.provider(&name).is_some() {
+        bail!(
+            "There already is a provider named '{}', pick another name or extend it in a rule file",
+            name
+        );
+    }
+    if draft.strip.is_empty() && draft.redirect.is_none() {
+        bail!("Nothing to do, choose parameters to strip or a redirect parameter");
+    }
+
+    let exceptions: Vec<String> = draft
+        .exceptions
+        .iter()
+        .map(|path| format!("/{}", path.trim_start_matches('/')))
+        .collect();
+
+    let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+    for param in &draft.strip {
+        if !pairs.iter().any(|(key, _)| key == param) {
+            bail!("The URL has no '{}' parameter", param);
+        }
+    }
+
+    let host_pattern = format!(r"^https?:\/\/(?:[a-z0-9-]+\.)*?{}", js_escape(&domain));
+    let page_pattern = format!(
+        r"^https?:\/\/(?:[a-z0-9-]+\.)*?{}{}",
+        js_escape(&host),
+        js_escape(url.path())
+    );
+
+    let spec = ProviderSpec {
+        url_pattern: Some(format!("{}(?:[:/?#]|$)", host_pattern)),
+        rules: draft.strip.iter().map(|param| js_escape(param)).collect(),
+        redirections: draft
+            .redirect
+            .iter()
+            .map(|param| format!(r"{}\?(?:.*?&)?{}=([^&]+)", page_pattern, js_escape(param)))
+            .collect(),
+        exceptions: exceptions
+            .iter()
+            .map(|path| format!(r"{}{}", host_pattern, js_escape(path)))
+            .collect(),
+        ..Default::default()
+    };
+    let file = RuleFile {
+        providers: [(name.clone(), spec)].into_iter().collect(),
+    };
+
+    // Expectations are what the existing rules already do, plus the change the provider is meant to make
+    let baseline = UrlCleaner::with_rules(CleaningOptions::default(), rules)?;
+    let mut cases = Vec::new();
+
+    if let Some(param) = &draft.redirect {
+        let target = pairs
+            .iter()
+            .find_map(|(key, value)| (key == param).then_some(value))
+            .with_context(|| format!("The URL has no '{}' parameter", param))?;
+        let target =
+            Url::parse(target).with_context(|| format!("The '{}' parameter isn't a URL", param))?;
+        cases.push(Case {
+            name: Some(format!("{} redirects to its {} parameter", name, param)),
+            url: example.to_string(),
+            options: Default::default(),
+            expect: Expectation {
+                url: Some(target.to_string()),
+                redirect: Some(true),
+                ..Default::default()
+            },
+        });
+    }
+
+    if !draft.strip.is_empty() {
+        // The redirect would fire first, so the stripping case goes without its parameter
+        let mut input = url.clone();
+        if let Some(param) = &draft.redirect {
+            without_params(&mut input, |key| key == param);
+        }
+        let mut expected = Url::parse(&baseline.clean_url(input.as_str())?.url)?;
+        without_params(&mut expected, |key| {
+            draft.strip.iter().any(|param| param == key)
+        });
+
+        cases.push(Case {
+            name: Some(format!("{} strips {}", name, draft.strip.join(", "))),
+            url: input.to_string(),
+            options: Default::default(),
+            expect: Expectation {
+                url: Some(expected.to_string()),
+                applied_rules: vec![name.clone()],
+                ..Default::default()
+            },
+        });
+    }
+
+    for path in &exceptions {
+        let mut input = url.clone();
+        input.set_path(path);
+        let expected = baseline.clean_url(input.as_str())?;
+
+        cases.push(Case {
+            name: Some(format!("{} leaves {} alone", name, path)),
+            url: input.to_string(),
+            options: Default::default(),
+            expect: Expectation {
+                url: Some(expected.url),
+                redirect: Some(false),
+                ..Default::default()
+            },
+        });
+    }
+
+    let cases = CaseFile {
+        options: Default::default(),
+        cases,
+    };
+
+    let mut with_provider = rules.clone();
+    with_provider.merge(&file, RuleSource::User("new provider".into()))?;
+    let results = cases.run(&with_provider)?;
+
+    Ok(Authored {
+        name,
+        file,
+        cases,
+        results,
+    })
+}
+
+/// Drop the matching parameters, rebuilding the query with the same `set_query_pairs` the cleaner uses, so the
+/// expectations are written the way `UrlCleaner` will produce them
+fn without_params(url: &mut Url, drop: impl Fn(&str) -> bool) {
+    let kept: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !drop(key))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    set_query_pairs(url, &kept);
+}
+
+/// Escape text for a ClearURLs (JavaScript) regex, including the `/` the ClearURLs data always escapes
+fn js_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if r"\^$.*+?()[]{}|/".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_author_provider() {
+        let rules = RuleSet::default();
+        let draft = Draft {
+            strip: vec!["trk".to_string()],
+            redirect: Some("to".to_string()),
+            exceptions: vec!["checkout".to_string()],
+            ..Default::default()
+        };
+
+        let authored = author(
+            "https://go.shop-x.test/out.php?id=1&trk=abc&to=https%3A%2F%2Fexample.com%2Fa",
+            &draft,
+            &rules,
+        )
+        .unwrap();
+
+        assert_eq!(authored.name, "shop-x");
+        let spec = &authored.file.providers["shop-x"];
+        assert_eq!(
+            spec.url_pattern.as_deref(),
+            Some(r"^https?:\/\/(?:[a-z0-9-]+\.)*?shop-x\.test(?:[:/?#]|$)")
+        );
+        assert_eq!(
+            spec.redirections,
+            vec![r"^https?:\/\/(?:[a-z0-9-]+\.)*?go\.shop-x\.test\/out\.php\?(?:.*?&)?to=([^&]+)"]
+        );
+        assert_eq!(
+            authored.cases.cases[1].expect.url.as_deref(),
+            Some("https://go.shop-x.test/out.php?id=1")
+        );
+        assert!(
+            authored.results.iter().all(CaseResult::passed),
+            "{:?}",
+            authored.results
+        );
+
+        let missing = Draft {
+            strip: vec!["nope".to_string()],
+            ..Default::default()
+        };
+        assert!(author("https://shop.test/?a=1", &missing, &rules).is_err());
+    }
+}
@@ -0,0 +1,234 @@
+//! Canonicalization, so URLs that only differ in how they're written compare equal.
+//!
+//! Parsing already lowercases http(s) hosts and drops their default ports, so those two are checked against the
+//! URL as it was typed. Everything else is applied to the parsed URL, and each step is named after its toggle.
+
+use url::{Host, Url};
+
+use crate::{Canonicalization, TrailingSlash};
+
+/// Canonicalize `url` (parsed from `input`), returning the normalizations that changed it
+pub(crate) fn canonicalize(
+    input: &str,
+    url: &mut Url,
+    options: &Canonicalization,
+) -> Vec<&'static str> {
+    let mut applied = Vec::new();
+    let (raw_host, raw_port) = authority(input);
+
+    if options.lowercase_host {
+        let typed = raw_host.is_some_and(|host| host.chars().any(|c| c.is_ascii_uppercase()));
+        let host = url.host_str().unwrap_or_default().to_string();
+        if host.chars().any(|c| c.is_ascii_uppercase()) {
+            let _ = url.set_host(Some(&host.to_ascii_lowercase()));
+            applied.push("lowercase_host");
+        } else if typed {
+            applied.push("lowercase_host");
+        }
+    }
+
+    if options.remove_default_port {
+        let default = match url.scheme() {
+            "http" | "ws" => Some(80),
+            "https" | "wss" => Some(443),
+            "ftp" => Some(21),
+            _ => None,
+        };
+        let typed = raw_port.and_then(|port| port.parse::<u16>().ok());
+        if default.is_some() && url.port() == default {
+            let _ = url.set_port(None);
+            applied.push("remove_default_port");
+        } else if default.is_some() && typed == default {
+            applied.push("remove_default_port");
+        }
+    }
+
+    if options.strip_trailing_dot
+        && let Some(Host::Domain(host)) = url.host()
+        && host.ends_with('.')
+    {
+        let host = host.trim_end_matches('.').to_string();
+        if !host.is_empty() && url.set_host(Some(&host)).is_ok() {
+            applied.push("strip_trailing_dot");
+        }
+    }
+
+    if options.strip_www
+        && let Some(Host::Domain(host)) = url.host()
+        && let Some(rest) = host.strip_prefix("www.")
+        && rest.contains('.')
+    {
+        let rest = rest.to_string();
+        if url.set_host(Some(&rest)).is_ok() {
+            applied.push("strip_www");
+        }
+    }
+
+    if options.normalize_percent_encoding {
+        let path = normalize_percent(url.path());
+        let query = url.query().map(normalize_percent);
+        let fragment = url.fragment().map(normalize_percent);
+        if path != url.path()
+            || query.as_deref() != url.query()
+            || fragment.as_deref() != url.fragment()
+        {
+            url.set_path(&path);
+            url.set_query(query.as_deref());
+            url.set_fragment(fragment.as_deref());
+            applied.push("normalize_percent_encoding");
+        }
+    }
+
+    if options.collapse_slashes && url.path().contains("//") {
+        let mut path = url.path().to_string();
+        while path.contains("//") {
+            path = path.replace("//", "/");
+        }
+        url.set_path(&path);
+        applied.push("collapse_slashes");
+    }
+
+    let path = url.path().to_string();
+    let last = path.rsplit('/').next().unwrap_or_default();
+    let trailing = match options.trailing_slash {
+        TrailingSlash::Keep => None,
+        // Files keep their name, `/style.css/` would be a different resource
+        TrailingSlash::Add if !path.ends_with('/') && !last.contains('.') => {
+            Some(format!("{}/", path))
+        }
+        TrailingSlash::Remove if path.len() > 1 && path.ends_with('/') => {
+            Some(path.trim_end_matches('/').to_string())
+        }
+        _ => None,
+    };
+    if let Some(path) = trailing {
+        url.set_path(&path);
+        applied.push("trailing_slash");
+    }
+
+    if let Some(query) = url.query() {
+        let mut params: Vec<&str> = query.split('&').collect();
+        let count = params.len();
+
+        if options.remove_empty_params {
+            params.retain(|param| !param.is_empty() && !param.ends_with('='));
+        }
+        let removed = params.len() != count;
+
+        let mut sorted = false;
+        if options.sort_params {
+            let before = params.clone();
+            // Stable, so repeated parameters keep their order
+            params.sort_by_key(|param| param.split('=').next().unwrap_or_default());
+            sorted = params != before;
+        }
+
+        if removed || sorted {
+            let query = params.join("&");
+            url.set_query((!query.is_empty()).then_some(query.as_str()));
+        }
+        if removed {
+            applied.push("remove_empty_params");
+        }
+        if sorted {
+            applied.push("sort_params");
+        }
+    }
+
+    applied
+}
+
+/// The host and port as they were typed, before parsing normalized them
+fn authority(input: &str) -> (Option<&str>, Option<&str>) {
+    let Some((_, rest)) = input.split_once("://") else {
+        return (None, None);
+    };
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_port = authority.rsplit('@').next().unwrap_or_default();
+
+    // IPv6 addresses have colons of their own
+    let (host, port) = match host_port.rfind(']') {
+        Some(end) => (&host_port[..=end], host_port[end + 1..].strip_prefix(':')),
+        None => match host_port.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host_port, None),
+        },
+    };
+    (Some(host), port)
+}
+
+/// Decode escaped unreserved characters and uppercase the hex digits of the rest
+fn normalize_percent(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut normalized = String::with_capacity(text.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && let Some(hex) = text.get(i + 1..i + 3)
+            && let Ok(byte) = u8::from_str_radix(hex, 16)
+        {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                normalized.push(byte as char);
+            } else {
+                normalized.push('%');
+                normalized.push_str(&hex.to_ascii_uppercase());
+            }
+            i += 3;
+            continue;
+        }
+
+        // Everything else is copied as is, a whole character at a time
+        let len = text[i..].chars().next().map_or(1, char::len_utf8);
+        normalized.push_str(&text[i..i + len]);
+        i += len;
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_canonicalize() {
+        let options = Canonicalization {
+            trailing_slash: TrailingSlash::Remove,
+            strip_www: true,
+            ..Canonicalization::standard()
+        };
+
+        let input = "https://WWW.Example.com.:443//a//b%7e%2f/?z=1&e=&a=%c3%a9&&a=0#x%41";
+        let mut url = Url::parse(input).unwrap();
+        let applied = canonicalize(input, &mut url, &options);
+
+        assert_eq!(
+            url.as_str(),
+            "https://example.com/a/b~%2F?a=%C3%A9&a=0&z=1#xA"
+        );
+        assert_eq!(
+            applied,
+            vec![
+                "lowercase_host",
+                "remove_default_port",
+                "strip_trailing_dot",
+                "strip_www",
+                "normalize_percent_encoding",
+                "collapse_slashes",
+                "trailing_slash",
+                "remove_empty_params",
+                "sort_params",
+            ]
+        );
+
+        // Nothing to do, nothing reported
+        let input = "https://example.com/a?b=1";
+        let mut url = Url::parse(input).unwrap();
+        assert!(canonicalize(input, &mut url, &options).is_empty());
+
+        assert_eq!(
+            authority("http://user@[::1]:8080/x"),
+            (Some("[::1]"), Some("8080"))
+        );
+    }
+}
@@ -0,0 +1,311 @@
+//! Test cases for rulesets, written as data (TOML, YAML or JSON) rather than Rust
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{Canonicalization, CleaningMode, CleaningOptions, CleaningResult, RuleSet, UrlCleaner};
+
+/// A file of test cases, with options shared by all of them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaseFile {
+    /// Options applied to every case, before the case's own
+    pub options: CaseOptions,
+    pub cases: Vec<Case>,
+}
+
+/// One URL to clean and what cleaning it should come down to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Case {
+    /// Shown in the report, defaults to the input URL
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub url: String,
+    #[serde(default)]
+    pub options: CaseOptions,
+    #[serde(default)]
+    pub expect: Expectation,
+}
+
+impl Case {
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.url)
+    }
+}
+
+/// Overrides for the default `CleaningOptions`, only the fields that are set are changed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CaseOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skip_localhost: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apply_referral_marketing: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain_blocking: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub additional_blocked_params: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub blacklisted_domains: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clean_fragments: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub strip_text_fragments: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unwrap_proxies: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub clean_nested: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mode: Option<CleaningMode>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowed_params: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heuristics: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub heuristic_threshold: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub minimal_form: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub canonicalize: Option<Canonicalization>,
+}
+
+impl CaseOptions {
+    /// Apply the overrides on top of `options`
+    pub fn apply(&self, options: &mut CleaningOptions) {
+        if let Some(skip_localhost) = self.skip_localhost {
+            options.skip_localhost = skip_localhost;
+        }
+        if let Some(apply_referral_marketing) = self.apply_referral_marketing {
+            options.apply_referral_marketing = apply_referral_marketing;
+        }
+        if let Some(domain_blocking) = self.domain_blocking {
+            options.domain_blocking = domain_blocking;
+        }
+        if let Some(params) = &self.additional_blocked_params {
+            options.additional_blocked_params = params.clone();
+        }
+        if let Some(domains) = &self.blacklisted_domains {
+            options.blacklisted_domains = domains.clone();
+        }
+        if let Some(clean_fragments) = self.clean_fragments {
+            options.clean_fragments = clean_fragments;
+        }
+        if let Some(strip_text_fragments) = self.strip_text_fragments {
+            options.strip_text_fragments = strip_text_fragments;
+        }
+        if let Some(unwrap_proxies) = self.unwrap_proxies {
+            options.unwrap_proxies = unwrap_proxies;
+        }
+        if let Some(clean_nested) = self.clean_nested {
+            options.clean_nested = clean_nested;
+        }
+        if let Some(mode) = self.mode {
+            options.mode = mode;
+        }
+        if let Some(params) = &self.allowed_params {
+            options.allowed_params = params.clone();
+        }
+        if let Some(heuristics) = self.heuristics {
+            options.heuristics = heuristics;
+        }
+        if let Some(threshold) = self.heuristic_threshold {
+            options.heuristic_threshold = Some(threshold);
+        }
+        if let Some(minimal_form) = self.minimal_form {
+            options.minimal_form = minimal_form;
+        }
+        if let Some(canonicalize) = &self.canonicalize {
+            options.canonicalize = canonicalize.clone();
+        }
+    }
+}
+
+/// What a case expects, only the fields that are set are checked
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Expectation {
+    /// The cleaned URL
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub changed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cancel: Option<bool>,
+    /// Entries that must all be in `CleaningResult::applied_rules`, in any order
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub applied_rules: Vec<String>,
+}
+
+/// A field whose value didn't meet the expectation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mismatch {
+    pub field: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// The outcome of a single case
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseResult {
+    pub name: String,
+    pub url: String,
+    pub mismatches: Vec<Mismatch>,
+    /// Set when the URL couldn't be cleaned at all
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.mismatches.is_empty() && self.error.is_none()
+    }
+}
+
+impl CaseFile {
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).context("Failed to parse JSON case file")
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        toml::from_str(toml).context("Failed to parse TOML case file")
+    }
+
+    pub fn from_yaml(yaml: &str) -> Result<Self> {
+        serde_yaml::from_str(yaml).context("Failed to parse YAML case file")
+    }
+
+    /// Read a case file, picking the format from the file extension (JSON unless it's `.toml`, `.yaml` or `.yml`)
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read case file {}", path.display()))?;
+
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+
+        match extension.as_deref() {
+            Some("toml") => Self::from_toml(&contents),
+            Some("yaml" | "yml") => Self::from_yaml(&contents),
+            _ => Self::from_json(&contents),
+        }
+        .with_context(|| format!("Invalid case file {}", path.display()))
+    }
+
+    /// Run every case against `rules`, in order
+    pub fn run(&self, rules: &RuleSet) -> Result<Vec<CaseResult>> {
+        let mut results = Vec::with_capacity(self.cases.len());
+
+        for case in &self.cases {
+            let mut options = CleaningOptions::default();
+            self.options.apply(&mut options);
+            case.options.apply(&mut options);
+
+            let cleaner = UrlCleaner::with_rules(options, rules)?;
+            let (mismatches, error) = match cleaner.clean_url(&case.url) {
+                Ok(result) => (check(&case.expect, &result), None),
+                Err(e) => (Vec::new(), Some(format!("{:#}", e))),
+            };
+
+            results.push(CaseResult {
+                name: case.name().to_string(),
+                url: case.url.clone(),
+                mismatches,
+                error,
+            });
+        }
+
+        Ok(results)
+    }
+}
+
+fn check(expect: &Expectation, result: &CleaningResult) -> Vec<Mismatch> {
+    let mut mismatches = Vec::new();
+    let mut compare = |field: &str, expected: String, actual: String| {
+        if expected != actual {
+            mismatches.push(Mismatch {
+                field: field.to_string(),
+                expected,
+                actual,
+            });
+        }
+    };
+
+    if let Some(url) = &expect.url {
+        compare("url", url.clone(), result.url.clone());
+    }
+    if let Some(changed) = expect.changed {
+        compare("changed", changed.to_string(), result.changed.to_string());
+    }
+    if let Some(redirect) = expect.redirect {
+        compare(
+            "redirect",
+            redirect.to_string(),
+            result.redirect.to_string(),
+        );
+    }
+    if let Some(cancel) = expect.cancel {
+        compare("cancel", cancel.to_string(), result.cancel.to_string());
+    }
+
+    let missing = expect
+        .applied_rules
+        .iter()
+        .any(|rule| !result.applied_rules.contains(rule));
+    if missing {
+        compare(
+            "applied_rules",
+            expect.applied_rules.join(", "),
+            result.applied_rules.join(", "),
+        );
+    }
+
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_cases_pass() {
+        let cases = CaseFile::from_toml(include_str!("../cases/builtin.toml")).unwrap();
+        assert!(!cases.cases.is_empty());
+
+        for result in cases.run(RuleSet::embedded()).unwrap() {
+            assert!(result.passed(), "{:?}", result);
+        }
+    }
+
+    #[test]
+    fn test_failing_case_reports_diff() {
+        let cases = CaseFile::from_yaml(
+            r#"
+options:
+  additional_blocked_params: [session_tag]
+cases:
+  - name: wrong expectation
+    url: https://example.com/?a=1&session_tag=2
+    expect:
+      url: https://example.com/
+      applied_rules: [additional_params]
+"#,
+        )
+        .unwrap();
+
+        let results = cases.run(RuleSet::embedded()).unwrap();
+        assert!(!results[0].passed());
+        assert_eq!(
+            results[0].mismatches,
+            vec![Mismatch {
+                field: "url".to_string(),
+                expected: "https://example.com/".to_string(),
+                actual: "https://example.com/?a=1".to_string(),
+            }]
+        );
+    }
+}
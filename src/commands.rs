@@ -0,0 +1,11 @@
+//! Implementations of the CLI subcommands
+
+pub mod dedup;
+pub mod diff;
+pub mod export;
+pub mod import;
+pub mod lint;
+pub mod mine;
+pub mod new;
+pub mod test;
+pub mod update;
@@ -0,0 +1,99 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, Read},
+    path::PathBuf,
+};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use plink::UrlCleaner;
+use serde::Serialize;
+
+use crate::{CleanArgs, RuleArgs};
+
+#[derive(Debug, Args)]
+pub struct DedupArgs {
+    /// File with one URL per line, stdin if left out
+    #[arg(value_name = "FILE")]
+    input: Option<PathBuf>,
+
+    /// Print every group with its key and members as JSON, instead of one URL per group
+    #[arg(long)]
+    json: bool,
+
+    #[command(flatten)]
+    rules: RuleArgs,
+
+    #[command(flatten)]
+    clean: CleanArgs,
+}
+
+/// URLs sharing a dedup key
+#[derive(Debug, Serialize)]
+struct Group {
+    key: String,
+    /// The first URL of the group, cleaned with its redirects followed
+    representative: String,
+    /// Every URL in the group, as given
+    urls: Vec<String>,
+}
+
+pub fn run(args: DedupArgs) -> Result<()> {
+    let rules = args.rules.rules()?;
+    let cleaner = UrlCleaner::with_rules(args.clean.options(), &rules)?;
+
+    let contents = match &args.input {
+        Some(path) => fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?,
+        None => {
+            let mut contents = String::new();
+            io::stdin()
+                .read_to_string(&mut contents)
+                .context("Failed to read stdin")?;
+            contents
+        }
+    };
+
+    let mut groups: Vec<Group> = Vec::new();
+    let mut by_key: HashMap<String, usize> = HashMap::new();
+    for url in contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    {
+        // URLs that can't be cleaned only match themselves
+        let (key, representative) = match cleaner.dedup_key(url) {
+            Ok((key, result)) => (key, result.url),
+            Err(e) => {
+                eprintln!("Keeping {} as is: {:#}", url, e);
+                (url.to_string(), url.to_string())
+            }
+        };
+
+        match by_key.get(&key) {
+            Some(&i) => groups[i].urls.push(url.to_string()),
+            None => {
+                by_key.insert(key.clone(), groups.len());
+                groups.push(Group {
+                    key,
+                    representative,
+                    urls: vec![url.to_string()],
+                });
+            }
+        }
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&groups)?);
+    } else {
+        for group in &groups {
+            println!("{}", group.representative);
+        }
+    }
+
+    let urls: usize = groups.iter().map(|group| group.urls.len()).sum();
+    eprintln!("{} URLs in {} groups", urls, groups.len());
+
+    Ok(())
+}
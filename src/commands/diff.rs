@@ -0,0 +1,159 @@
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use plink::{
+    RuleFile, RuleSet, RuleSource,
+    diff::{self, BehaviourChange, Outcome, RulesetDiff},
+};
+use serde::Serialize;
+
+use crate::CleanArgs;
+
+#[derive(Debug, Args)]
+pub struct DiffArgs {
+    /// The old ruleset file, or `built-in` for the embedded rules
+    #[arg(value_name = "OLD")]
+    old: String,
+
+    /// The new ruleset file, or `built-in` for the embedded rules
+    #[arg(value_name = "NEW")]
+    new: String,
+
+    /// File with one URL per line to clean with both rulesets
+    #[arg(long, value_name = "FILE")]
+    corpus: Option<String>,
+
+    #[command(flatten)]
+    clean: CleanArgs,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// Exit with 1 when the rulesets behave differently on the corpus (or differ at all, without one)
+    #[arg(long)]
+    exit_code: bool,
+}
+
+#[derive(Serialize)]
+struct Report {
+    structure: RulesetDiff,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    behaviour: Option<Vec<BehaviourChange>>,
+}
+
+pub fn run(args: DiffArgs) -> Result<()> {
+    let (old_file, old_rules) = load(&args.old)?;
+    let (new_file, new_rules) = load(&args.new)?;
+
+    let behaviour = match &args.corpus {
+        Some(corpus) => {
+            let urls = read_corpus(Path::new(corpus))?;
+            let options = args.clean.options();
+            Some(diff::diff_behaviour(
+                &old_rules, &new_rules, &options, &urls,
+            )?)
+        }
+        None => None,
+    };
+
+    let report = Report {
+        structure: diff::diff_files(&old_file, &new_file),
+        behaviour,
+    };
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_report(&report);
+    }
+
+    let differs = match &report.behaviour {
+        Some(changes) => !changes.is_empty(),
+        None => !report.structure.is_empty(),
+    };
+    if args.exit_code && differs {
+        bail!("rulesets differ");
+    }
+
+    Ok(())
+}
+
+/// A rule file along with the ruleset compiled from it
+fn load(arg: &str) -> Result<(RuleFile, RuleSet)> {
+    if arg == "built-in" {
+        let embedded = RuleSet::embedded();
+        return Ok((embedded.to_file(), embedded.clone()));
+    }
+
+    let path = Path::new(arg);
+    let file = RuleFile::load(path)?;
+    let rules = RuleSet::from_file(&file, RuleSource::User(arg.into()))?;
+    Ok((file, rules))
+}
+
+/// One URL per line, skipping blank lines and `#` comments
+pub fn read_corpus(path: &Path) -> Result<Vec<String>> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read corpus {}", path.display()))?;
+
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+fn describe(outcome: &Outcome) -> String {
+    if outcome.error {
+        format!("error: {}", outcome.url)
+    } else if outcome.cancel {
+        "cancelled".to_string()
+    } else if outcome.redirect {
+        format!("redirect to {}", outcome.url)
+    } else {
+        outcome.url.clone()
+    }
+}
+
+fn print_report(report: &Report) {
+    let structure = &report.structure;
+
+    for name in &structure.added {
+        println!("+ provider {}", name);
+    }
+    for name in &structure.removed {
+        println!("- provider {}", name);
+    }
+    for provider in &structure.changed {
+        println!("~ provider {}", provider.name);
+        for change in &provider.changes {
+            for value in &change.removed {
+                println!("    - {}: {}", change.field, value);
+            }
+            for value in &change.added {
+                println!("    + {}: {}", change.field, value);
+            }
+        }
+    }
+
+    println!(
+        "{} added, {} removed, {} changed providers",
+        structure.added.len(),
+        structure.removed.len(),
+        structure.changed.len()
+    );
+
+    if let Some(changes) = &report.behaviour {
+        for change in changes {
+            println!();
+            println!("{}", change.input);
+            println!("  old: {}", describe(&change.old));
+            println!("  new: {}", describe(&change.new));
+        }
+        println!();
+        println!("{} URLs behave differently", changes.len());
+    }
+}
@@ -0,0 +1,113 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use plink::export::{adblock, blocklist, dnr};
+
+use crate::{CleanArgs, RuleArgs};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum FormatArg {
+    /// Chrome `declarativeNetRequest` rules (JSON)
+    Dnr,
+    /// uBlock Origin filter list
+    Ublock,
+    /// AdGuard filter list
+    Adguard,
+    /// Hosts file of the blocked domains
+    Hosts,
+    /// dnsmasq `address=` lines for the blocked domains
+    Dnsmasq,
+    /// unbound `local-zone` lines for the blocked domains
+    Unbound,
+    /// Response policy zone of the blocked domains
+    Rpz,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportArgs {
+    /// Format to export to
+    #[arg(long, value_enum)]
+    format: FormatArg,
+
+    /// Write the export here instead of printing it
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    rules: RuleArgs,
+
+    #[command(flatten)]
+    clean: CleanArgs,
+
+    /// Maximum number of declarativeNetRequest rules
+    #[arg(long, value_name = "N", default_value_t = dnr::MAX_RULES)]
+    max_rules: usize,
+
+    /// Maximum number of declarativeNetRequest rules using a regexFilter
+    #[arg(long, value_name = "N", default_value_t = dnr::MAX_REGEX_RULES)]
+    max_regex_rules: usize,
+
+    /// Maximum memory a regexFilter may need once compiled, in bytes
+    #[arg(long, value_name = "BYTES", default_value_t = dnr::MAX_REGEX_MEMORY)]
+    max_regex_memory: usize,
+}
+
+pub fn run(args: ExportArgs) -> Result<()> {
+    let rules = args.rules.rules()?;
+    let options = args.clean.options();
+
+    let (contents, unsupported) = match args.format {
+        FormatArg::Dnr => {
+            let limits = dnr::Limits {
+                max_rules: args.max_rules,
+                max_regex_rules: args.max_regex_rules,
+                max_regex_memory: args.max_regex_memory,
+            };
+            let export = dnr::export(&rules, &options, &limits)?;
+            eprintln!("Exported {} rules", export.rules.len());
+
+            (
+                serde_json::to_string_pretty(&export.rules)?,
+                export.unsupported,
+            )
+        }
+        FormatArg::Ublock | FormatArg::Adguard => {
+            let flavor = match args.format {
+                FormatArg::Adguard => adblock::Flavor::Adguard,
+                _ => adblock::Flavor::Ublock,
+            };
+            let export = adblock::export(&rules, &options, flavor)?;
+
+            (export.list, export.unsupported)
+        }
+        FormatArg::Hosts | FormatArg::Dnsmasq | FormatArg::Unbound | FormatArg::Rpz => {
+            let format = match args.format {
+                FormatArg::Hosts => blocklist::Format::Hosts,
+                FormatArg::Dnsmasq => blocklist::Format::Dnsmasq,
+                FormatArg::Unbound => blocklist::Format::Unbound,
+                _ => blocklist::Format::Rpz,
+            };
+            let export = blocklist::export(&rules, &options, format)?;
+            eprintln!("Exported {} domains", export.domains.len());
+
+            (export.list, export.unsupported)
+        }
+    };
+
+    for skipped in &unsupported {
+        eprintln!(
+            "{} {} `{}` [{}]: {}",
+            skipped.provider, skipped.field, skipped.pattern, skipped.source, skipped.reason
+        );
+    }
+    eprintln!("{} patterns left out", unsupported.len());
+
+    match &args.output {
+        Some(path) => fs::write(path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?,
+        None => println!("{}", contents),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use plink::import::{self, Format};
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum FormatArg {
+    Adblock,
+    Firefox,
+    Brave,
+}
+
+impl From<FormatArg> for Format {
+    fn from(format: FormatArg) -> Self {
+        match format {
+            FormatArg::Adblock => Format::Adblock,
+            FormatArg::Firefox => Format::Firefox,
+            FormatArg::Brave => Format::Brave,
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct ImportArgs {
+    /// The list to convert
+    #[arg(value_name = "FILE")]
+    file: PathBuf,
+
+    /// Format of the list
+    #[arg(long, value_enum)]
+    format: FormatArg,
+
+    /// Write the providers here (TOML if it ends in `.toml`, JSON otherwise) instead of printing them
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+pub fn run(args: ImportArgs) -> Result<()> {
+    let import = import::import_path(&args.file, args.format.into())?;
+
+    for unsupported in &import.unsupported {
+        let location = match unsupported.line {
+            0 => args.file.display().to_string(),
+            line => format!("{}:{}", args.file.display(), line),
+        };
+        eprintln!("{}: {}: {}", location, unsupported.reason, unsupported.text);
+    }
+    eprintln!(
+        "Imported {} providers, {} unsupported entries",
+        import.file.providers.len(),
+        import.unsupported.len()
+    );
+
+    let is_toml = args
+        .output
+        .as_ref()
+        .and_then(|path| path.extension())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+
+    let contents = if is_toml {
+        toml::to_string_pretty(&import.file)?
+    } else {
+        serde_json::to_string_pretty(&import.file)?
+    };
+
+    match &args.output {
+        Some(path) => fs::write(path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?,
+        None => println!("{}", contents),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+use clap::Args;
+use plink::{
+    RuleSet,
+    lint::{self, LintIssue, LintKind, LintReport, Severity},
+};
+
+#[derive(Debug, Args)]
+pub struct LintArgs {
+    /// Ruleset files to lint (ClearURLs JSON or TOML), the embedded rules when none are given
+    #[arg(value_name = "FILE")]
+    files: Vec<PathBuf>,
+
+    /// Also lint the embedded rules when files are given
+    #[arg(long)]
+    embedded: bool,
+
+    /// Print the report as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+pub fn run(args: LintArgs) -> Result<()> {
+    let mut reports = Vec::new();
+
+    if args.files.is_empty() || args.embedded {
+        let embedded = RuleSet::embedded();
+        let mut report = lint::lint_file(&embedded.to_file());
+        report.source = "built-in".to_string();
+
+        // Patterns the build script already had to leave out never make it into the ruleset
+        report
+            .issues
+            .extend(embedded.skipped().iter().map(|skipped| LintIssue {
+                severity: Severity::Warning,
+                kind: LintKind::InvalidRegex,
+                provider: skipped.provider.clone(),
+                field: Some(skipped.field.clone()),
+                pattern: Some(skipped.pattern.clone()),
+                message: format!("skipped: {}", skipped.reason),
+            }));
+        reports.push(report);
+    }
+
+    for path in &args.files {
+        reports.push(lint::lint_path(path)?);
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        reports.iter().for_each(print_report);
+    }
+
+    let errors: usize = reports.iter().map(LintReport::errors).sum();
+    if errors > 0 {
+        bail!("{} lint error(s)", errors);
+    }
+
+    Ok(())
+}
+
+fn print_report(report: &LintReport) {
+    for issue in &report.issues {
+        println!("{}: {}", report.source, issue);
+    }
+
+    println!(
+        "{}: {} providers, {} patterns, {} errors, {} warnings",
+        report.source,
+        report.providers,
+        report.patterns,
+        report.errors(),
+        report.warnings()
+    );
+}
@@ -0,0 +1,87 @@
+use std::{fs, path::PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use plink::mine;
+
+use crate::{CleanArgs, RuleArgs, commands::diff::read_corpus};
+
+#[derive(Debug, Args)]
+pub struct MineArgs {
+    /// File with one URL per line
+    #[arg(value_name = "CORPUS")]
+    corpus: PathBuf,
+
+    /// Write the candidate providers here instead of printing them
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    #[command(flatten)]
+    rules: RuleArgs,
+
+    #[command(flatten)]
+    clean: CleanArgs,
+
+    /// Ignore parameters seen fewer times than this on a domain
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    min_count: usize,
+
+    /// Ignore parameters scoring less than this (0 to 1)
+    #[arg(long, value_name = "SCORE", default_value_t = 0.5)]
+    min_score: f64,
+
+    /// Print the ranked candidates with their statistics as JSON, instead of the providers
+    #[arg(long)]
+    json: bool,
+}
+
+pub fn run(args: MineArgs) -> Result<()> {
+    let rules = args.rules.rules()?;
+    let urls = read_corpus(&args.corpus)?;
+
+    let report = mine::mine(
+        &rules,
+        &args.clean.options(),
+        &urls,
+        args.min_count,
+        args.min_score,
+    )?;
+
+    eprintln!(
+        "{} candidates from {} of {} URLs",
+        report.candidates.len(),
+        report.urls,
+        urls.len()
+    );
+    for candidate in &report.candidates {
+        eprintln!(
+            "  {:.2} {} {}: seen {} times ({:.0}% of URLs), {} values, {:.1} bits{}",
+            candidate.score,
+            candidate.domain,
+            candidate.param,
+            candidate.occurrences,
+            candidate.frequency * 100.0,
+            candidate.distinct_values,
+            candidate.entropy,
+            if candidate.varies {
+                ", varies on identical URLs"
+            } else {
+                ""
+            }
+        );
+    }
+
+    let contents = if args.json {
+        serde_json::to_string_pretty(&report.candidates)?
+    } else {
+        serde_json::to_string_pretty(&report.file)?
+    };
+
+    match &args.output {
+        Some(path) => fs::write(path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?,
+        None => println!("{}", contents),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use std::{
+    fs,
+    io::{self, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use clap::Args;
+use plink::author::{self, Draft};
+
+use crate::{RuleArgs, parse_csv};
+
+#[derive(Debug, Args)]
+pub struct NewArgs {
+    /// An example URL the provider should clean
+    #[arg(value_name = "URL")]
+    url: String,
+
+    /// Provider name, defaults to the domain's name
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Comma-separated list of params to strip
+    #[arg(long, value_name = "PARAMS")]
+    strip: Option<String>,
+
+    /// The param holding the URL to redirect to
+    #[arg(long, value_name = "PARAM")]
+    redirect: Option<String>,
+
+    /// Comma-separated list of path prefixes to leave alone
+    #[arg(long = "except", value_name = "PATHS")]
+    exceptions: Option<String>,
+
+    /// Write the provider here (TOML if it ends in `.toml`, JSON otherwise) instead of printing it
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+
+    /// Write the test cases here (TOML, YAML or JSON by extension) instead of printing them to stderr
+    #[arg(long, value_name = "FILE")]
+    cases: Option<PathBuf>,
+
+    #[command(flatten)]
+    rules: RuleArgs,
+}
+
+pub fn run(args: NewArgs) -> Result<()> {
+    let rules = args.rules.rules()?;
+
+    let mut draft = Draft {
+        name: args.name.clone(),
+        strip: parse_csv(args.strip.as_deref()),
+        redirect: args.redirect.clone(),
+        exceptions: parse_csv(args.exceptions.as_deref()),
+    };
+    if draft.strip.is_empty() && draft.redirect.is_none() && io::stdin().is_terminal() {
+        ask(&args.url, &mut draft)?;
+    }
+
+    let authored = author::author(&args.url, &draft, &rules)?;
+
+    let provider = match &args.output {
+        Some(path) if is_toml(path) => toml::to_string_pretty(&authored.file)?,
+        _ => serde_json::to_string_pretty(&authored.file)?,
+    };
+    match &args.output {
+        Some(path) => fs::write(path, provider)
+            .with_context(|| format!("Failed to write {}", path.display()))?,
+        None => println!("{}", provider),
+    }
+
+    match &args.cases {
+        Some(path) => {
+            let extension = path.extension().and_then(|ext| ext.to_str());
+            let cases = match extension {
+                Some("toml") => toml::to_string_pretty(&authored.cases)?,
+                Some("yaml" | "yml") => serde_yaml::to_string(&authored.cases)?,
+                _ => serde_json::to_string_pretty(&authored.cases)?,
+            };
+            fs::write(path, cases)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        None => eprintln!("{}", toml::to_string_pretty(&authored.cases)?),
+    }
+
+    let mut failed = 0;
+    for result in &authored.results {
+        if result.passed() {
+            eprintln!("PASS {}", result.name);
+            continue;
+        }
+
+        failed += 1;
+        eprintln!("FAIL {}", result.name);
+        if let Some(error) = &result.error {
+            eprintln!("  error: {}", error);
+        }
+        for mismatch in &result.mismatches {
+            eprintln!("  {}", mismatch.field);
+            eprintln!("    - {}", mismatch.expected);
+            eprintln!("    + {}", mismatch.actual);
+        }
+    }
+    if failed > 0 {
+        bail!("The provider failed {} of its own case(s)", failed);
+    }
+
+    Ok(())
+}
+
+/// Fill in the draft by asking about each parameter of the URL
+fn ask(url: &str, draft: &mut Draft) -> Result<()> {
+    let params = author::params(url)?;
+    if params.is_empty() {
+        bail!("{} has no query parameters to choose from", url);
+    }
+
+    eprintln!("Parameters of {}:", url);
+    for (i, (key, value)) in params.iter().enumerate() {
+        eprintln!("  {}. {}={}", i + 1, key, value);
+    }
+
+    // Answers are numbers from the list or parameter names
+    let pick = |answer: &str| -> Vec<String> {
+        parse_csv(Some(answer))
+            .into_iter()
+            .map(|item| match item.parse::<usize>() {
+                Ok(n) if (1..=params.len()).contains(&n) => params[n - 1].0.clone(),
+                _ => item,
+            })
+            .collect()
+    };
+
+    draft.strip = pick(&prompt("Strip (numbers or names, comma separated)")?);
+    draft.redirect = pick(&prompt("Redirect target parameter (empty for none)")?)
+        .into_iter()
+        .next();
+    draft.exceptions = parse_csv(Some(&prompt(
+        "Paths to leave alone (comma separated, empty for none)",
+    )?));
+
+    Ok(())
+}
+
+fn prompt(question: &str) -> Result<String> {
+    eprint!("{}: ", question);
+    io::stderr().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    Ok(answer.trim().to_string())
+}
+
+fn is_toml(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+}
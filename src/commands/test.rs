@@ -0,0 +1,70 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+use clap::Args;
+use plink::cases::{CaseFile, CaseResult};
+
+use crate::RuleArgs;
+
+#[derive(Debug, Args)]
+pub struct TestArgs {
+    /// Case files (TOML, YAML or JSON)
+    #[arg(value_name = "CASES", required = true)]
+    files: Vec<PathBuf>,
+
+    #[command(flatten)]
+    rules: RuleArgs,
+
+    /// Print the results as JSON
+    #[arg(long)]
+    json: bool,
+}
+
+pub fn run(args: TestArgs) -> Result<()> {
+    let rules = args.rules.rules()?;
+
+    let mut results = Vec::new();
+    for path in &args.files {
+        results.extend(CaseFile::load(path)?.run(&rules)?);
+    }
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&results)?);
+    } else {
+        for result in &results {
+            print_result(result);
+        }
+    }
+
+    let failed = results.iter().filter(|r| !r.passed()).count();
+    if !args.json {
+        println!();
+        println!("{} passed, {} failed", results.len() - failed, failed);
+    }
+
+    if failed > 0 {
+        bail!("{} case(s) failed", failed);
+    }
+
+    Ok(())
+}
+
+fn print_result(result: &CaseResult) {
+    if result.passed() {
+        println!("PASS {}", result.name);
+        return;
+    }
+
+    println!("FAIL {}", result.name);
+    if result.name != result.url {
+        println!("  input: {}", result.url);
+    }
+    if let Some(error) = &result.error {
+        println!("  error: {}", error);
+    }
+    for mismatch in &result.mismatches {
+        println!("  {}", mismatch.field);
+        println!("    - {}", mismatch.expected);
+        println!("    + {}", mismatch.actual);
+    }
+}
@@ -0,0 +1,70 @@
+use anyhow::{Result, anyhow};
+use clap::Args;
+use plink::update::{self, RulesCache};
+
+/// Where ClearURLs publishes its ruleset
+const DEFAULT_SOURCE: &str = "https://rules2.clearurls.xyz/data.minify.json";
+
+#[derive(Debug, Args)]
+pub struct UpdateArgs {
+    /// Ruleset to fetch: a path, `file://` URL or `http(s)://` URL
+    #[arg(long, value_name = "URL|FILE", default_value = DEFAULT_SOURCE)]
+    from: String,
+
+    /// SHA-256 hash file to verify against, defaults to ClearURLs' `rules.minify.hash` or `<from>.sha256`
+    #[arg(long, value_name = "URL|FILE")]
+    hash: Option<String>,
+
+    /// Install the ruleset even if some of its patterns fail to compile
+    #[arg(long)]
+    force: bool,
+}
+
+fn open_cache() -> Result<RulesCache> {
+    RulesCache::open_default().ok_or_else(|| {
+        anyhow!(
+            "No cache directory found, set {} to choose one",
+            update::CACHE_DIR_ENV
+        )
+    })
+}
+
+pub fn run(args: UpdateArgs) -> Result<()> {
+    let cache = open_cache()?;
+    let report = update::update(&cache, &args.from, args.hash.as_deref(), args.force)?;
+
+    for error in &report.lint_errors {
+        eprintln!("{}", error);
+    }
+
+    if report.unchanged {
+        println!(
+            "Rules are up to date ({}, {} providers)",
+            report.installed.id, report.installed.providers
+        );
+        return Ok(());
+    }
+
+    println!(
+        "Installed rules {} ({} providers) into {}",
+        report.installed.id,
+        report.installed.providers,
+        cache.dir().display()
+    );
+    if let Some(previous) = &report.previous {
+        println!("Previous rules {} kept for rollback", previous.id);
+    }
+
+    Ok(())
+}
+
+pub fn rollback() -> Result<()> {
+    let cache = open_cache()?;
+    let current = cache.rollback()?;
+
+    println!(
+        "Rolled back to rules {} ({} providers) from {}",
+        current.id, current.providers, current.source
+    );
+    Ok(())
+}
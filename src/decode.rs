@@ -0,0 +1,199 @@
+//! Decoding of redirect targets, which redirectors encode in more ways than the percent-encoding ClearURLs
+//! expects: twice percent-encoded, HTML escaped (`&amp;`), or base64 like Bing's `u=a1<base64url>`.
+//!
+//! Decoders are tried one step at a time until the capture is an http(s) URL, and the steps are kept for
+//! `--explain`.
+
+use anyhow::{Context, Result};
+use url::Url;
+
+/// Decoding steps tried before giving up on a capture
+const MAX_STEPS: usize = 8;
+
+/// Prefixes in front of base64 targets, like Bing's `a1`
+const BASE64_PREFIXES: &[&str] = &["a1"];
+
+/// The URL a redirection captured, along with the decoding steps it took
+pub(crate) fn redirect_target(capture: &str) -> Result<(Url, Vec<&'static str>)> {
+    let mut value = capture.to_string();
+    let mut steps = Vec::new();
+
+    // Captures are percent-encoded to begin with, even when they look like URLs
+    if value.contains('%') {
+        value = percent(&value).context("Failed to decode redirect URL")?;
+        steps.push("percent");
+    }
+
+    for _ in 0..MAX_STEPS {
+        if let Some(decoded) = html_entities(&value) {
+            value = decoded;
+            steps.push("html_entities");
+            continue;
+        }
+
+        if value.starts_with("http://") || value.starts_with("https://") {
+            let url = Url::parse(&value).context("Invalid redirect URL")?;
+            return Ok((url, steps));
+        }
+
+        if value.contains('%')
+            && let Some(decoded) = percent(&value)
+        {
+            value = decoded;
+            steps.push("percent");
+            continue;
+        }
+
+        let prefixed = BASE64_PREFIXES
+            .iter()
+            .find_map(|prefix| value.strip_prefix(prefix).and_then(base64_url));
+        if let Some(decoded) = prefixed {
+            value = decoded;
+            steps.push("prefixed_base64");
+            continue;
+        }
+
+        if let Some(decoded) = base64_url(&value) {
+            value = decoded;
+            steps.push("base64");
+            continue;
+        }
+
+        break;
+    }
+
+    anyhow::bail!("Invalid redirect URL, couldn't decode '{}'", capture)
+}
+
+fn percent(value: &str) -> Option<String> {
+    let decoded = urlencoding::decode(value).ok()?;
+    (decoded != value).then(|| decoded.into_owned())
+}
+
+/// Decode the HTML escapes a redirector left in, if there are any
+fn html_entities(value: &str) -> Option<String> {
+    if !value.contains('&') {
+        return None;
+    }
+
+    let mut decoded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest
+            .find(';')
+            .filter(|end| *end <= 10)
+            .and_then(|end| Some((entity(&rest[1..end])?, end)));
+        match entity {
+            Some((c, end)) => {
+                decoded.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+
+    (decoded != value).then_some(decoded)
+}
+
+fn entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        _ => {
+            let number = name.strip_prefix('#')?;
+            let code = match number.strip_prefix(['x', 'X']) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                None => number.parse().ok()?,
+            };
+            char::from_u32(code)
+        }
+    }
+}
+
+/// Decode base64 or base64url, with or without padding, if it decodes to an http(s) URL (possibly still
+/// percent-encoded)
+fn base64_url(value: &str) -> Option<String> {
+    let body = value.trim_end_matches('=');
+    if body.len() < 8 || body.len() % 4 == 1 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(body.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in body.bytes() {
+        let sextet = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | u32::from(sextet);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+
+    let decoded = String::from_utf8(bytes).ok()?;
+    decoded.starts_with("http").then_some(decoded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded(capture: &str) -> (String, Vec<&'static str>) {
+        let (url, steps) = redirect_target(capture).unwrap();
+        (url.to_string(), steps)
+    }
+
+    #[test]
+    fn test_redirect_target() {
+        assert_eq!(
+            decoded("https%3A%2F%2Fexample.com%2Fa%3Fb%3D%252F"),
+            ("https://example.com/a?b=%2F".to_string(), vec!["percent"])
+        );
+        assert_eq!(
+            decoded("https%253A%252F%252Fexample.com%252F"),
+            (
+                "https://example.com/".to_string(),
+                vec!["percent", "percent"]
+            )
+        );
+        assert_eq!(
+            decoded("https://example.com/?a=1&amp;b=2"),
+            (
+                "https://example.com/?a=1&b=2".to_string(),
+                vec!["html_entities"]
+            )
+        );
+        // Bing's `a1` prefix in front of base64url without padding
+        assert_eq!(
+            decoded("a1aHR0cHM6Ly9leGFtcGxlLmNvbS9wYWdlP2lkPTE"),
+            (
+                "https://example.com/page?id=1".to_string(),
+                vec!["prefixed_base64"]
+            )
+        );
+        assert_eq!(
+            decoded("aHR0cHM6Ly9leGFtcGxlLmNvbS8="),
+            ("https://example.com/".to_string(), vec!["base64"])
+        );
+
+        assert!(redirect_target("not-a-url").is_err());
+    }
+}
@@ -0,0 +1,235 @@
+//! Comparing two rulesets, both structurally and by what they do to a corpus of URLs
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{CleaningOptions, CleaningResult, ProviderSpec, RuleFile, RuleSet, UrlCleaner};
+
+/// Structural differences between two rule files
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RulesetDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<ProviderDiff>,
+}
+
+impl RulesetDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// The fields that changed in a provider present in both rulesets
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderDiff {
+    pub name: String,
+    pub changes: Vec<FieldChange>,
+}
+
+/// Values added to and removed from one field of a provider (the old and new value, for single valued fields)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    pub field: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// What cleaning a URL came down to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Outcome {
+    /// The cleaned URL, or the error if cleaning failed
+    pub url: String,
+    pub redirect: bool,
+    pub cancel: bool,
+    #[serde(default)]
+    pub error: bool,
+}
+
+impl From<anyhow::Result<CleaningResult>> for Outcome {
+    fn from(result: anyhow::Result<CleaningResult>) -> Self {
+        match result {
+            Ok(result) => Self {
+                url: result.url,
+                redirect: result.redirect,
+                cancel: result.cancel,
+                error: false,
+            },
+            Err(e) => Self {
+                url: e.to_string(),
+                redirect: false,
+                cancel: false,
+                error: true,
+            },
+        }
+    }
+}
+
+/// A URL the two rulesets disagree on
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BehaviourChange {
+    pub input: String,
+    pub old: Outcome,
+    pub new: Outcome,
+}
+
+/// Compare the providers of two rule files by name
+pub fn diff_files(old: &RuleFile, new: &RuleFile) -> RulesetDiff {
+    let mut diff = RulesetDiff::default();
+
+    for name in new.providers.keys() {
+        if !old.providers.contains_key(name) {
+            diff.added.push(name.clone());
+        }
+    }
+
+    for (name, old_spec) in &old.providers {
+        match new.providers.get(name) {
+            None => diff.removed.push(name.clone()),
+            Some(new_spec) => {
+                let changes = diff_provider(old_spec, new_spec);
+                if !changes.is_empty() {
+                    diff.changed.push(ProviderDiff {
+                        name: name.clone(),
+                        changes,
+                    });
+                }
+            }
+        }
+    }
+
+    diff
+}
+
+fn diff_provider(old: &ProviderSpec, new: &ProviderSpec) -> Vec<FieldChange> {
+    let single = |value: Option<String>| value.into_iter().collect::<Vec<_>>();
+    let flag = |value: Option<bool>| vec![value.unwrap_or(false).to_string()];
+    let value_rules = |spec: &ProviderSpec| {
+        spec.value_rules
+            .iter()
+            .map(|rule| format!("{}={}", rule.key, rule.value))
+            .collect::<Vec<_>>()
+    };
+
+    let fields = [
+        (
+            "urlPattern",
+            single(old.url_pattern()),
+            single(new.url_pattern()),
+        ),
+        ("rules", old.param_rules(), new.param_rules()),
+        ("valueRules", value_rules(old), value_rules(new)),
+        ("rawRules", old.raw_rules.clone(), new.raw_rules.clone()),
+        ("exceptions", old.exceptions.clone(), new.exceptions.clone()),
+        (
+            "redirections",
+            old.redirections.clone(),
+            new.redirections.clone(),
+        ),
+        (
+            "referralMarketing",
+            old.referral_marketing.clone(),
+            new.referral_marketing.clone(),
+        ),
+        (
+            "completeProvider",
+            flag(old.complete_provider),
+            flag(new.complete_provider),
+        ),
+        (
+            "forceRedirection",
+            flag(old.force_redirection),
+            flag(new.force_redirection),
+        ),
+    ];
+
+    fields
+        .into_iter()
+        .filter_map(|(field, old, new)| {
+            let added: Vec<String> = new.iter().filter(|v| !old.contains(v)).cloned().collect();
+            let removed: Vec<String> = old.iter().filter(|v| !new.contains(v)).cloned().collect();
+
+            (!added.is_empty() || !removed.is_empty()).then(|| FieldChange {
+                field: field.to_string(),
+                added,
+                removed,
+            })
+        })
+        .collect()
+}
+
+/// Clean every URL with both rulesets, returning the ones whose URL, redirect or cancel status differs
+pub fn diff_behaviour(
+    old: &RuleSet,
+    new: &RuleSet,
+    options: &CleaningOptions,
+    urls: &[String],
+) -> anyhow::Result<Vec<BehaviourChange>> {
+    let old_cleaner = UrlCleaner::with_rules(options.clone(), old)?;
+    let new_cleaner = UrlCleaner::with_rules(options.clone(), new)?;
+
+    let changes = urls
+        .par_iter()
+        .filter_map(|url| {
+            let old = Outcome::from(old_cleaner.clean_url(url));
+            let new = Outcome::from(new_cleaner.clean_url(url));
+
+            (old != new).then(|| BehaviourChange {
+                input: url.clone(),
+                old,
+                new,
+            })
+        })
+        .collect();
+
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RuleSource;
+
+    #[test]
+    fn test_diff_rulesets() {
+        let old = RuleFile::from_json(
+            r#"{"providers": {
+                "shop": {"urlPattern": "^https?:\\/\\/shop\\.test", "rules": ["ref", "tag"]},
+                "gone": {"urlPattern": "^https?:\\/\\/gone\\.test", "completeProvider": true}
+            }}"#,
+        )
+        .unwrap();
+        let new = RuleFile::from_json(
+            r#"{"providers": {
+                "shop": {"urlPattern": "^https?:\\/\\/shop\\.test", "rules": ["ref", "campaign"]},
+                "fresh": {"urlPattern": "^https?:\\/\\/fresh\\.test", "rules": ["x"]}
+            }}"#,
+        )
+        .unwrap();
+
+        let diff = diff_files(&old, &new);
+        assert_eq!(diff.added, vec!["fresh"]);
+        assert_eq!(diff.removed, vec!["gone"]);
+        assert_eq!(
+            diff.changed[0].changes,
+            vec![FieldChange {
+                field: "rules".to_string(),
+                added: vec!["campaign".to_string()],
+                removed: vec!["tag".to_string()],
+            }]
+        );
+
+        let old = RuleSet::from_file(&old, RuleSource::BuiltIn).unwrap();
+        let new = RuleSet::from_file(&new, RuleSource::BuiltIn).unwrap();
+        let urls = vec![
+            "https://shop.test/?ref=1&tag=2&campaign=3".to_string(),
+            "https://gone.test/".to_string(),
+            "https://other.test/?tag=1".to_string(),
+        ];
+
+        let changes = diff_behaviour(&old, &new, &CleaningOptions::default(), &urls).unwrap();
+        assert_eq!(changes.len(), 2);
+        assert_eq!(changes[0].old.url, "https://shop.test/?campaign=3");
+        assert_eq!(changes[0].new.url, "https://shop.test/?tag=2");
+        assert!(changes[1].old.cancel && !changes[1].new.cancel);
+    }
+}
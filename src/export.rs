@@ -0,0 +1,444 @@
+//! Converting a ruleset into formats other tools can enforce
+
+use std::fmt;
+
+use crate::{CleaningMode, CleaningOptions, Provider, Rule, SkippedRule, ValueRule};
+
+pub mod adblock;
+pub mod blocklist;
+pub mod dnr;
+
+/// Patterns expanding to more names than this are treated as open-ended
+const MAX_NAMES: usize = 64;
+
+/// The regex ClearURLs (and `domains` entries like `google.*`) use for "any top level domain"
+const ANY_TLD: &str = r"(?:\.[a-z]{2,}){1,}";
+
+/// Stands in for `ANY_TLD` while expanding a host
+const ANY_TLD_MARKER: char = '\u{1}';
+
+/// What a urlPattern can end with after the host, while still matching the whole host
+const HOST_ENDINGS: &[&str] = &[
+    "",
+    "$",
+    "(?:[:/?#]|$)",
+    "(?:[/?#]|$)",
+    r"(?:\/|$)",
+    r"\/",
+    "/",
+    r"\/.*",
+    "/.*",
+];
+
+/// Ways urlPatterns allow any number of subdomains
+const SUBDOMAINS: &[&str] = &[
+    r"(?:[a-z0-9-]+\.)*?",
+    r"(?:[a-z0-9-]+\.)*",
+    r"([a-z0-9-]+\.)*?",
+    r"([a-z0-9-]+\.)*",
+];
+
+const HOST_PATTERN: &str = "the host is a pattern rather than a list of names";
+
+/// A domain a urlPattern is limited to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PatternDomain {
+    /// The host name, without the top level domain when `any_tld` is set
+    pub name: String,
+    /// Whether subdomains match too
+    pub subdomains: bool,
+    /// Whether any top level domain matches, like `google.*`
+    pub any_tld: bool,
+}
+
+impl fmt::Display for PatternDomain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.subdomains {
+            write!(f, "*.")?;
+        }
+        write!(f, "{}", self.name)?;
+        if self.any_tld {
+            write!(f, ".*")?;
+        }
+        Ok(())
+    }
+}
+
+/// The domains a urlPattern is limited to, or why it can't be reduced to domains.
+///
+/// This understands the shape of the ClearURLs patterns and the ones built from `domains`:
+/// `^https?:\/\/(?:[a-z0-9-]+\.)*?(?:amazon|google)(?:\.[a-z]{2,}){1,}`, optionally followed by the end of the host.
+pub fn pattern_domains(pattern: &str) -> Result<Vec<PatternDomain>, &'static str> {
+    let (domains, whole_host) = host_domains(pattern)?;
+    if !whole_host {
+        return Err("only matches some paths");
+    }
+    Ok(domains)
+}
+
+/// The domains a URL pattern's host is limited to, and whether it matches every URL on them rather than
+/// only some paths
+fn host_domains(pattern: &str) -> Result<(Vec<PatternDomain>, bool), &'static str> {
+    if pattern == ".*" || pattern.is_empty() {
+        return Err("matches every URL");
+    }
+
+    let rest = pattern
+        .strip_prefix('^')
+        .ok_or("not anchored to the start of the URL")?;
+    let rest = ["https?", "https", "http"]
+        .iter()
+        .find_map(|scheme| rest.strip_prefix(scheme))
+        .and_then(|rest| {
+            rest.strip_prefix(r":\/\/")
+                .or_else(|| rest.strip_prefix("://"))
+        })
+        .ok_or("doesn't start with an http(s) scheme")?;
+
+    let (subdomains, rest) = match SUBDOMAINS
+        .iter()
+        .find_map(|prefix| rest.strip_prefix(prefix))
+    {
+        Some(rest) => (true, rest),
+        None => (false, rest),
+    };
+
+    let (host, ending) = split_host(rest);
+    if host.is_empty() {
+        return Err(HOST_PATTERN);
+    }
+    let whole_host = HOST_ENDINGS.contains(&ending);
+
+    let host = host.replace(ANY_TLD, &ANY_TLD_MARKER.to_string());
+    let names = expand(&host, true).ok_or(HOST_PATTERN)?;
+
+    names
+        .into_iter()
+        .map(|name| {
+            let (name, any_tld) = match name.strip_suffix(ANY_TLD_MARKER) {
+                Some(name) => (name.to_ascii_lowercase(), true),
+                None => (name.to_ascii_lowercase(), false),
+            };
+
+            let valid = !name.is_empty()
+                && !name.starts_with(['.', '-'])
+                && !name.ends_with(['.', '-'])
+                && (any_tld || name.contains('.'))
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-');
+            if !valid {
+                return Err(HOST_PATTERN);
+            }
+
+            Ok(PatternDomain {
+                name,
+                subdomains,
+                any_tld,
+            })
+        })
+        .collect::<Result<_, _>>()
+        .map(|domains| (domains, whole_host))
+}
+
+impl PatternDomain {
+    /// Whether every host this matches is matched by `other` too
+    fn within(&self, other: &PatternDomain) -> bool {
+        if self.subdomains && !other.subdomains {
+            return false;
+        }
+        if self.any_tld {
+            other.any_tld && under(&self.name, &other.name, other.subdomains)
+        } else {
+            other.takes_in(&self.name)
+        }
+    }
+
+    /// Whether some host is matched by both
+    fn overlaps(&self, other: &PatternDomain) -> bool {
+        match (self.any_tld, other.any_tld) {
+            (true, false) => self.takes_in(&other.name),
+            (false, true) => other.takes_in(&self.name),
+            _ => {
+                under(&self.name, &other.name, other.subdomains)
+                    || under(&other.name, &self.name, self.subdomains)
+            }
+        }
+    }
+
+    /// Whether this matches the literal host `host`
+    fn takes_in(&self, host: &str) -> bool {
+        if !self.any_tld {
+            return under(host, &self.name, self.subdomains);
+        }
+        let suffixes = std::iter::once(host)
+            .chain(host.match_indices('.').map(|(i, _)| &host[i + 1..]))
+            .take(if self.subdomains { usize::MAX } else { 1 });
+        suffixes
+            .filter_map(|suffix| suffix.strip_prefix(self.name.as_str()))
+            .any(|tld| tld.len() > 1 && tld.starts_with('.'))
+    }
+}
+
+/// Whether `name` is `parent`, or one of its subdomains when `subdomains` is set
+fn under(name: &str, parent: &str, subdomains: bool) -> bool {
+    name == parent
+        || subdomains
+            && name
+                .strip_suffix(parent)
+                .is_some_and(|sub| sub.ends_with('.'))
+}
+
+/// Split the host part off a urlPattern (after the scheme and subdomains), at the first top level `/`, `?`,
+/// `:`, `$` or character class
+fn split_host(pattern: &str) -> (&str, &str) {
+    let bytes = pattern.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if depth == 0 && matches!(bytes.get(i + 1), Some(b'/' | b'?' | b':')) => break,
+            b'\\' => i += 1,
+            b'/' | b'$' | b':' | b'[' if depth == 0 => break,
+            b'(' if depth == 0 && pattern[i..].starts_with("(?:[") => break,
+            b'(' => depth += 1,
+            b')' => depth -= 1,
+            _ => {}
+        }
+        i += 1;
+    }
+
+    pattern.split_at(i.min(bytes.len()))
+}
+
+/// The finite set of names a parameter rule matches, when it's made of literals, `(?:a|b)` groups and `?`
+fn param_names(pattern: &str) -> Option<Vec<String>> {
+    // ClearURLs prefixes names with an optional encoded `?`, which only shows up in mangled query strings
+    let pattern = pattern.strip_prefix("(?:%3F)?").unwrap_or(pattern);
+    expand(pattern, false)
+}
+
+/// Every string a pattern of literals, `(?:a|b)` groups and `?` matches. With `literal_dots`, an unescaped
+/// `.` is taken to mean a dot, the way it's usually meant in host names.
+fn expand(pattern: &str, literal_dots: bool) -> Option<Vec<String>> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut pos = 0;
+    let names = alternation(&chars, &mut pos, literal_dots)?;
+    (pos == chars.len() && names.iter().all(|name| !name.is_empty())).then_some(names)
+}
+
+fn alternation(chars: &[char], pos: &mut usize, literal_dots: bool) -> Option<Vec<String>> {
+    let mut names = sequence(chars, pos, literal_dots)?;
+    while chars.get(*pos) == Some(&'|') {
+        *pos += 1;
+        names.extend(sequence(chars, pos, literal_dots)?);
+    }
+    (names.len() <= MAX_NAMES).then_some(names)
+}
+
+fn sequence(chars: &[char], pos: &mut usize, literal_dots: bool) -> Option<Vec<String>> {
+    let mut names = vec![String::new()];
+
+    while let Some(&c) = chars.get(*pos) {
+        let mut atom = match c {
+            '|' | ')' => break,
+            '(' => {
+                if chars.get(*pos + 1..*pos + 3) != Some(&['?', ':']) {
+                    return None;
+                }
+                *pos += 3;
+                let inner = alternation(chars, pos, literal_dots)?;
+                if chars.get(*pos) != Some(&')') {
+                    return None;
+                }
+                *pos += 1;
+                inner
+            }
+            '\\' => {
+                let escaped = *chars.get(*pos + 1)?;
+                if escaped.is_ascii_alphanumeric() {
+                    return None;
+                }
+                *pos += 2;
+                vec![escaped.to_string()]
+            }
+            '.' if literal_dots => {
+                *pos += 1;
+                vec![".".to_string()]
+            }
+            '.' | '^' | '$' | '*' | '+' | '?' | '[' | ']' | '{' | '}' => return None,
+            c => {
+                *pos += 1;
+                vec![c.to_string()]
+            }
+        };
+
+        if chars.get(*pos) == Some(&'?') {
+            *pos += 1;
+            atom.push(String::new());
+        }
+
+        if names.len() * atom.len() > MAX_NAMES {
+            return None;
+        }
+        names = names
+            .iter()
+            .flat_map(|prefix| {
+                atom.iter()
+                    .map(move |suffix| format!("{}{}", prefix, suffix))
+            })
+            .collect();
+    }
+
+    Some(names)
+}
+
+fn push_unique(names: &mut Vec<String>, name: String) {
+    if !names.contains(&name) {
+        names.push(name);
+    }
+}
+
+/// A provider's rule that couldn't be exported
+fn skipped(provider: &Provider, field: &str, rule: &Rule, reason: &str) -> SkippedRule {
+    SkippedRule {
+        provider: provider.name.clone(),
+        field: field.to_string(),
+        pattern: rule.as_str().to_string(),
+        reason: reason.to_string(),
+        source: rule.source.to_string(),
+    }
+}
+
+/// A provider's value rule that couldn't be exported, as `key=value`
+fn skipped_value(provider: &Provider, rule: &ValueRule, reason: &str) -> SkippedRule {
+    SkippedRule {
+        pattern: rule.pattern(),
+        ..skipped(provider, "valueRules", &rule.key, reason)
+    }
+}
+
+/// A `CleaningOptions` setting that couldn't be exported
+fn from_options(field: &str, pattern: &str) -> SkippedRule {
+    SkippedRule {
+        provider: "options".to_string(),
+        field: field.to_string(),
+        pattern: pattern.to_string(),
+        reason: String::new(),
+        source: "options".to_string(),
+    }
+}
+
+/// The options no export format can express, since they decide on parameters no rule names
+fn dynamic_options(options: &CleaningOptions) -> Vec<SkippedRule> {
+    let mut skipped = Vec::new();
+    if options.mode == CleaningMode::Strict {
+        skipped.push(SkippedRule {
+            reason: "keeping only allowlisted parameters can't be exported".to_string(),
+            ..from_options("mode", "strict")
+        });
+    }
+    if options.heuristics
+        && let Some(threshold) = options.heuristic_threshold
+    {
+        skipped.push(SkippedRule {
+            reason: "removing parameters by heuristic score can't be exported".to_string(),
+            ..from_options("heuristic_threshold", &threshold.to_string())
+        });
+    }
+    if options.minimal_form {
+        skipped.push(SkippedRule {
+            reason: "minimal forms rewrite the path, which can't be exported".to_string(),
+            ..from_options("minimal_form", "on")
+        });
+    }
+    if options.canonicalize.is_enabled() {
+        skipped.push(SkippedRule {
+            reason: "canonicalizing URLs can't be exported".to_string(),
+            ..from_options("canonicalize", "on")
+        });
+    }
+    skipped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProviderSpec;
+
+    #[test]
+    fn test_param_names() {
+        assert_eq!(
+            param_names("(?:%3F)?yclid"),
+            Some(vec!["yclid".to_string()])
+        );
+        assert_eq!(
+            param_names(r"fb_action_(?:types|ids)"),
+            Some(vec![
+                "fb_action_types".to_string(),
+                "fb_action_ids".to_string()
+            ])
+        );
+        assert_eq!(
+            param_names(r"ref_?src"),
+            Some(vec!["ref_src".to_string(), "refsrc".to_string()])
+        );
+        assert_eq!(param_names(r"utm_[a-z]+"), None);
+        assert_eq!(param_names(r"\d+"), None);
+    }
+
+    #[test]
+    fn test_pattern_domains() {
+        let domains = |pattern: &str| {
+            pattern_domains(pattern)
+                .map(|domains| domains.iter().map(ToString::to_string).collect::<Vec<_>>())
+        };
+
+        assert_eq!(
+            domains(r"^https?:\/\/(?:[a-z0-9-]+\.)*?amazon(?:\.[a-z]{2,}){1,}"),
+            Ok(vec!["*.amazon.*".to_string()])
+        );
+        assert_eq!(
+            domains(r"^https?:\/\/(?:[a-z0-9-]+\.)*?twitter.com"),
+            Ok(vec!["*.twitter.com".to_string()])
+        );
+        assert_eq!(
+            domains(r"^https?:\/\/(?:www\.)?(?:youtube|youtu)\.be\/"),
+            Ok(vec![
+                "www.youtube.be".to_string(),
+                "www.youtu.be".to_string(),
+                "youtube.be".to_string(),
+                "youtu.be".to_string()
+            ])
+        );
+
+        let spec = ProviderSpec {
+            domains: vec!["shop.test".to_string(), "google.*".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            domains(&spec.url_pattern().unwrap()),
+            Ok(vec!["*.shop.test".to_string(), "*.google.*".to_string()])
+        );
+
+        assert_eq!(domains(".*"), Err("matches every URL"));
+        assert_eq!(
+            domains(r"^https?:\/\/(?:[a-z0-9-]+\.)*?google\.com\/url\?"),
+            Err("only matches some paths")
+        );
+        assert_eq!(domains(r"^https?:\/\/[^/]+\.test"), Err(HOST_PATTERN));
+
+        let (checkout, whole_host) =
+            host_domains(r"^https?:\/\/(?:www\.)?shop\.test\/checkout").unwrap();
+        assert!(!whole_host);
+        let (sites, _) = host_domains(&spec.url_pattern().unwrap()).unwrap();
+        assert!(checkout.iter().all(|d| sites.iter().any(|s| d.within(s))));
+        assert!(sites[1].overlaps(&PatternDomain {
+            name: "maps.google.co.uk".to_string(),
+            subdomains: false,
+            any_tld: false,
+        }));
+        assert!(!sites[0].within(&checkout[1]));
+    }
+}
@@ -0,0 +1,361 @@
+//! uBlock Origin / AdGuard filter lists, the reverse of `import::adblock`.
+//!
+//! - Parameter rules become `$removeparam` filters, by name where the rule only matches a few names and as a
+//!   `/^(?:rule)=/i` regex otherwise
+//! - Complete providers become block filters (plus `$document` ones for AdGuard), when `domain_blocking` is on
+//! - Exceptions, `blacklisted_domains` and `skip_localhost` become `@@` filters, which for parameter providers
+//!   only keep the provider's own parameters, the way a ClearURLs exception only turns off its own provider
+//!
+//! URL patterns that reduce to plain domains are written as `||domain^` (or an exact host `/regex/` when they
+//! don't take in subdomains), the rest as `/regex/` filters, which both blockers run as JavaScript regexes just
+//! like ClearURLs. Redirections, raw rules and value rules can't be expressed.
+
+use std::fmt::{self, Write};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    ANY_TLD, PatternDomain, dynamic_options, from_options, param_names, pattern_domains, skipped,
+    skipped_value,
+};
+use crate::{CleaningOptions, Provider, RuleSet, SkippedRule, neat_url::NeatRule};
+
+/// Which blocker the list is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Flavor {
+    /// uBlock Origin, which blocks pages with a plain filter
+    Ublock,
+    /// AdGuard, which needs `$document` to block pages
+    Adguard,
+}
+
+impl fmt::Display for Flavor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Flavor::Ublock => "ublock",
+            Flavor::Adguard => "adguard",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The filter list, along with every pattern that couldn't be expressed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdblockExport {
+    pub list: String,
+    pub unsupported: Vec<SkippedRule>,
+}
+
+/// Write a ruleset as a filter list, cleaning the way `UrlCleaner` would with `options`
+pub fn export(rules: &RuleSet, options: &CleaningOptions, flavor: Flavor) -> Result<AdblockExport> {
+    let mut list = String::new();
+    let mut unsupported = Vec::new();
+
+    let info = rules.info();
+    writeln!(list, "! Title: plink rules ({})", flavor)?;
+    writeln!(list, "! Source: {}", info.source)?;
+    if let Some(sha256) = &info.sha256 {
+        writeln!(list, "! SHA-256: {}", sha256)?;
+    }
+
+    unsupported.extend(dynamic_options(options));
+    let mut option_filters = Vec::new();
+    if options.skip_localhost {
+        option_filters.push(
+            r"@@/^https?:\/\/(?:localhost(?:[:/]|$)|127\.|192\.168\.|10\.|172\.)/$removeparam"
+                .to_string(),
+        );
+    }
+    for domain in &options.blacklisted_domains {
+        option_filters.push(format!("@@||{}^$removeparam", domain));
+    }
+    for rule in &options.additional_blocked_params {
+        match NeatRule::parse(rule)?.global_param() {
+            Some(name) => option_filters.push(format!("$removeparam={}", escape_option(name))),
+            None => unsupported.push(SkippedRule {
+                reason: "only plain parameter names without a domain can be exported".to_string(),
+                ..from_options("additional_blocked_params", rule)
+            }),
+        }
+    }
+    if !option_filters.is_empty() {
+        writeln!(list, "\n! options")?;
+        for filter in option_filters {
+            writeln!(list, "{}", filter)?;
+        }
+    }
+
+    for provider in rules.providers() {
+        let filters = provider_filters(provider, options, flavor, &mut unsupported);
+        if filters.is_empty() {
+            continue;
+        }
+
+        writeln!(list, "\n! {} [{}]", provider.name, provider.source)?;
+        for filter in filters {
+            writeln!(list, "{}", filter)?;
+        }
+    }
+
+    Ok(AdblockExport { list, unsupported })
+}
+
+fn provider_filters(
+    provider: &Provider,
+    options: &CleaningOptions,
+    flavor: Flavor,
+    unsupported: &mut Vec<SkippedRule>,
+) -> Vec<String> {
+    let mut filters = Vec::new();
+
+    let Some(targets) = targets(provider.url_pattern.as_str()) else {
+        unsupported.push(skipped(
+            provider,
+            "urlPattern",
+            &provider.url_pattern,
+            DOLLAR_REASON,
+        ));
+        return filters;
+    };
+
+    // The `$removeparam` values for the parameter rules
+    let values: Vec<String> = provider
+        .rules
+        .iter()
+        .flat_map(|rule| match param_names(rule.as_str()) {
+            Some(names) => names.iter().map(|name| escape_option(name)).collect(),
+            // Blockers match the regex against `name=value`, where ClearURLs matches the whole name
+            None => vec![format!("/^(?:{})=/i", escape_option(rule.as_str()))],
+        })
+        .collect();
+
+    for exception in &provider.exceptions {
+        let Some(regex) = regex_pattern(exception.as_str()) else {
+            unsupported.push(skipped(provider, "exceptions", exception, DOLLAR_REASON));
+            continue;
+        };
+
+        // An exception to a complete provider unblocks the URL, to any other provider keeps its own parameters,
+        // leaving the other providers' filters running
+        if provider.complete_provider {
+            filters.push(format!("@@{}", regex));
+        } else {
+            for value in &values {
+                filters.push(format!("@@{}$removeparam={}", regex, value));
+            }
+        }
+    }
+
+    if provider.complete_provider {
+        if !options.domain_blocking {
+            return filters;
+        }
+        if targets == [""] {
+            unsupported.push(skipped(
+                provider,
+                "urlPattern",
+                &provider.url_pattern,
+                "blocks every URL",
+            ));
+            return filters;
+        }
+
+        for target in &targets {
+            filters.push(target.clone());
+            if flavor == Flavor::Adguard {
+                filters.push(format!("{}$document", target));
+            }
+        }
+        return filters;
+    }
+
+    for redirection in &provider.redirections {
+        unsupported.push(skipped(
+            provider,
+            "redirections",
+            redirection,
+            "filter lists can't unwrap redirects",
+        ));
+    }
+    for raw_rule in &provider.raw_rules {
+        unsupported.push(skipped(
+            provider,
+            "rawRules",
+            raw_rule,
+            "filter lists can't rewrite URLs",
+        ));
+    }
+    for value_rule in &provider.value_rules {
+        // `$removeparam` regexes see the encoded value, and can't make only the key case insensitive
+        unsupported.push(skipped_value(
+            provider,
+            value_rule,
+            "filter lists can't match decoded values",
+        ));
+    }
+
+    for target in &targets {
+        for value in &values {
+            filters.push(format!("{}$removeparam={}", target, value));
+        }
+    }
+
+    filters
+}
+
+const DOLLAR_REASON: &str = "contains `$`, which filter syntax reads as the start of the options";
+
+/// The filter patterns covering a urlPattern: `||domain^` for each domain with its subdomains, a `/regex/`, or
+/// an empty pattern for every URL
+fn targets(url_pattern: &str) -> Option<Vec<String>> {
+    if url_pattern == ".*" {
+        return Some(vec![String::new()]);
+    }
+
+    match pattern_domains(url_pattern) {
+        // `||` takes in subdomains, so domains without them are matched exactly with a regex
+        Ok(domains) if domains.iter().all(|domain| !domain.any_tld) => Some(
+            domains
+                .iter()
+                .map(|domain| {
+                    if domain.subdomains {
+                        format!("||{}^", domain.name)
+                    } else {
+                        domains_regex(std::slice::from_ref(domain))
+                    }
+                })
+                .collect(),
+        ),
+        // Blockers only take `example.*` in `domain=`, which is about the page rather than the request
+        Ok(domains) => Some(vec![domains_regex(&domains)]),
+        Err(_) => regex_pattern(url_pattern).map(|regex| vec![regex]),
+    }
+}
+
+/// A `/regex/` filter pattern matching the domains, ending at the host since browsers always add a path
+fn domains_regex(domains: &[PatternDomain]) -> String {
+    let alternatives = domains
+        .iter()
+        .map(|domain| {
+            let subdomains = if domain.subdomains {
+                r"(?:[a-z0-9-]+\.)*?"
+            } else {
+                ""
+            };
+            let tld = if domain.any_tld { ANY_TLD } else { "" };
+            format!(r"{}{}{}", subdomains, domain.name.replace('.', r"\."), tld)
+        })
+        .collect::<Vec<_>>()
+        .join("|");
+
+    format!(r"/^https?:\/\/(?:{})[:\/?#]/", alternatives)
+}
+
+/// A URL regex as a `/regex/` filter pattern, unless it'd be mistaken for options
+fn regex_pattern(pattern: &str) -> Option<String> {
+    (!pattern.contains('$')).then(|| format!("/{}/", pattern))
+}
+
+/// Escape the commas that would otherwise end an option value
+fn escape_option(value: &str) -> String {
+    value.replace(',', r"\,")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RuleFile, RuleSource, import};
+
+    #[test]
+    fn test_export_adblock() {
+        let file = RuleFile::from_json(
+            r#"{"providers": {
+                "globalRules": {"urlPattern": ".*", "rules": ["(?:%3F)?utm_source", "ga_[a-z_]+"]},
+                "shop": {
+                    "domains": ["shop.test"],
+                    "rules": ["tag"],
+                    "exceptions": ["^https?:\\/\\/shop\\.test\\/checkout"],
+                    "redirections": ["^https?:\\/\\/shop\\.test\\/out\\?to=([^&]+)"]
+                },
+                "search": {"domains": ["search.*"], "rules": ["sid"]},
+                "ads": {"domains": ["ads.test"], "completeProvider": true}
+            }}"#,
+        )
+        .unwrap();
+        let rules = RuleSet::from_file(&file, RuleSource::User("test.json".into())).unwrap();
+
+        let options = CleaningOptions {
+            skip_localhost: false,
+            ..Default::default()
+        };
+        let export = export(&rules, &options, Flavor::Adguard).unwrap();
+        let filters: Vec<&str> = export
+            .list
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('!'))
+            .collect();
+
+        assert_eq!(
+            filters,
+            vec![
+                "||ads.test^",
+                "||ads.test^$document",
+                "$removeparam=utm_source",
+                "$removeparam=/^(?:ga_[a-z_]+)=/i",
+                r"/^https?:\/\/(?:(?:[a-z0-9-]+\.)*?search(?:\.[a-z]{2,}){1,})[:\/?#]/$removeparam=sid",
+                r"@@/^https?:\/\/shop\.test\/checkout/$removeparam=tag",
+                "||shop.test^$removeparam=tag",
+            ]
+        );
+        assert!(export.list.contains("! shop [test.json]"));
+        assert_eq!(export.unsupported.len(), 1);
+
+        // Importing the list again gives back the parameter rules
+        let reimported = import::adblock::import(&export.list);
+        assert!(
+            reimported.file.providers["adblock:shop.test"]
+                .params
+                .contains(&"tag".to_string())
+        );
+    }
+
+    #[test]
+    fn test_exceptions_stay_with_their_provider() {
+        let file = RuleFile::from_json(
+            r#"{"providers": {
+                "globalRules": {
+                    "urlPattern": ".*",
+                    "rules": ["utm_source"],
+                    "exceptions": ["^https?:\\/\\/(?:[a-z0-9-]+\\.)*?video\\.test\\/"]
+                },
+                "video": {"domains": ["video.test"], "rules": ["si"]},
+                "exact": {"urlPattern": "^https?:\\/\\/exact\\.test", "rules": ["ref"]}
+            }}"#,
+        )
+        .unwrap();
+        let rules = RuleSet::from_file(&file, RuleSource::BuiltIn).unwrap();
+        let options = CleaningOptions {
+            skip_localhost: false,
+            ..Default::default()
+        };
+        let export = export(&rules, &options, Flavor::Ublock).unwrap();
+        let filters: Vec<&str> = export
+            .list
+            .lines()
+            .filter(|line| !line.is_empty() && !line.starts_with('!'))
+            .collect();
+
+        // The global exception only keeps the global parameters, `si` is still removed on video.test
+        assert_eq!(
+            filters,
+            vec![
+                r"/^https?:\/\/(?:exact\.test)[:\/?#]/$removeparam=ref",
+                r"@@/^https?:\/\/(?:[a-z0-9-]+\.)*?video\.test\//$removeparam=utm_source",
+                "$removeparam=utm_source",
+                "||video.test^$removeparam=si",
+            ]
+        );
+    }
+}
@@ -0,0 +1,238 @@
+//! DNS blocklists of the domains complete providers block, for enforcing them at the resolver.
+//!
+//! Domains come from the providers' urlPatterns (see `pattern_domains`). Providers whose pattern matches more
+//! than whole domains, any top level domain or that have exceptions are reported instead, since a resolver can
+//! only block a name outright. Hosts files don't cover subdomains; the other formats do, and dnsmasq and unbound
+//! can't leave them out, so domains whose pattern doesn't cover subdomains are reported for those two.
+
+use std::fmt::{self, Write};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use super::{from_options, pattern_domains, skipped};
+use crate::{CleaningOptions, RuleSet, SkippedRule};
+
+/// A blocklist format
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// `0.0.0.0 example.com`
+    Hosts,
+    /// `address=/example.com/0.0.0.0`
+    Dnsmasq,
+    /// `local-zone: "example.com." always_nxdomain`
+    Unbound,
+    /// A response policy zone, answering NXDOMAIN
+    Rpz,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Hosts => "hosts",
+            Format::Dnsmasq => "dnsmasq",
+            Format::Unbound => "unbound",
+            Format::Rpz => "rpz",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The blocklist, along with the domains in it and every provider that couldn't be reduced to domains
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BlocklistExport {
+    pub list: String,
+    pub domains: Vec<String>,
+    pub unsupported: Vec<SkippedRule>,
+}
+
+/// Write the domains of the complete providers as a blocklist, skipping `blacklisted_domains` the way
+/// `UrlCleaner` does. Nothing is blocked without `domain_blocking`.
+pub fn export(
+    rules: &RuleSet,
+    options: &CleaningOptions,
+    format: Format,
+) -> Result<BlocklistExport> {
+    let comment = if format == Format::Rpz { ";" } else { "#" };
+    let info = rules.info();
+
+    let mut list = String::new();
+    let mut domains = Vec::new();
+    let mut unsupported = Vec::new();
+
+    writeln!(list, "{} plink blocklist ({})", comment, format)?;
+    writeln!(list, "{} Source: {}", comment, info.source)?;
+    if let Some(sha256) = &info.sha256 {
+        writeln!(list, "{} SHA-256: {}", comment, sha256)?;
+    }
+
+    if format == Format::Rpz {
+        // Serial numbers only need to go up, which the build (or fetch) time does
+        let serial = info.timestamp.unwrap_or(1) % (1 << 32);
+        writeln!(list, "$TTL 300")?;
+        writeln!(
+            list,
+            "@ IN SOA localhost. root.localhost. ({} 3600 600 86400 300)",
+            serial
+        )?;
+        writeln!(list, "  IN NS localhost.")?;
+    }
+
+    if !options.domain_blocking {
+        return Ok(BlocklistExport {
+            list,
+            domains,
+            unsupported,
+        });
+    }
+
+    let providers = rules.providers().iter().filter(|p| p.complete_provider);
+    for provider in providers {
+        let provider_domains = if provider.exceptions.is_empty() {
+            pattern_domains(provider.url_pattern.as_str())
+        } else {
+            Err("has exceptions, which blocking the whole domain would ignore")
+        }
+        .and_then(|found| {
+            if found.iter().any(|domain| domain.any_tld) {
+                Err("matches any top level domain, which can't be listed")
+            } else {
+                Ok(found)
+            }
+        });
+
+        let provider_domains = match provider_domains {
+            Ok(found) => found,
+            Err(reason) => {
+                unsupported.push(skipped(
+                    provider,
+                    "urlPattern",
+                    &provider.url_pattern,
+                    reason,
+                ));
+                continue;
+            }
+        };
+
+        writeln!(
+            list,
+            "\n{} {} [{}]",
+            comment, provider.name, provider.source
+        )?;
+        for domain in provider_domains {
+            let name = domain.name;
+            if domains.contains(&name) {
+                continue;
+            }
+            if let Some(blacklisted) = options
+                .blacklisted_domains
+                .iter()
+                .find(|blacklisted| name.ends_with(blacklisted.as_str()))
+            {
+                unsupported.push(SkippedRule {
+                    reason: format!("{} is in blacklisted_domains", name),
+                    ..from_options("blacklisted_domains", blacklisted)
+                });
+                continue;
+            }
+
+            // Both block every name under the domain, which a pattern without subdomains doesn't
+            if !domain.subdomains && matches!(format, Format::Dnsmasq | Format::Unbound) {
+                let reason = format!(
+                    "{} only matches itself, {} would block its subdomains too",
+                    name, format
+                );
+                unsupported.push(skipped(
+                    provider,
+                    "urlPattern",
+                    &provider.url_pattern,
+                    &reason,
+                ));
+                continue;
+            }
+
+            match format {
+                Format::Hosts => writeln!(list, "0.0.0.0 {}", name)?,
+                Format::Dnsmasq => writeln!(list, "address=/{}/0.0.0.0", name)?,
+                Format::Unbound => writeln!(list, "local-zone: \"{}.\" always_nxdomain", name)?,
+                Format::Rpz => {
+                    writeln!(list, "{} CNAME .", name)?;
+                    if domain.subdomains {
+                        writeln!(list, "*.{} CNAME .", name)?;
+                    }
+                }
+            }
+            domains.push(name);
+        }
+    }
+
+    Ok(BlocklistExport {
+        list,
+        domains,
+        unsupported,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RuleFile, RuleSource};
+
+    #[test]
+    fn test_export_blocklists() {
+        let file = RuleFile::from_json(
+            r#"{"providers": {
+                "ads": {"domains": ["ads.test", "track.test"], "completeProvider": true},
+                "cdn": {"urlPattern": "^https?:\\/\\/cdn\\.test\\/ads\\/", "completeProvider": true},
+                "metrics": {"domains": ["metrics.*"], "completeProvider": true},
+                "exact": {"urlPattern": "^https?:\\/\\/exact\\.test", "completeProvider": true},
+                "shop": {"domains": ["shop.test"], "rules": ["tag"]}
+            }}"#,
+        )
+        .unwrap();
+        let rules = RuleSet::from_file(&file, RuleSource::User("test.json".into())).unwrap();
+        let options = CleaningOptions {
+            blacklisted_domains: vec!["track.test".to_string()],
+            ..Default::default()
+        };
+
+        let export = export(&rules, &options, Format::Rpz).unwrap();
+        assert_eq!(export.domains, vec!["ads.test", "exact.test"]);
+        assert!(
+            export
+                .list
+                .contains("ads.test CNAME .\n*.ads.test CNAME .\n")
+        );
+        assert!(export.list.contains("exact.test CNAME .\n"));
+        assert!(!export.list.contains("*.exact.test"));
+
+        let reasons: Vec<&str> = export
+            .unsupported
+            .iter()
+            .map(|skipped| skipped.reason.as_str())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "track.test is in blacklisted_domains",
+                "only matches some paths",
+                "matches any top level domain, which can't be listed"
+            ]
+        );
+
+        let unbound = super::export(&rules, &options, Format::Unbound).unwrap();
+        assert!(
+            unbound
+                .list
+                .contains("local-zone: \"ads.test.\" always_nxdomain")
+        );
+        assert!(!unbound.list.contains("exact.test"));
+        assert!(
+            unbound
+                .unsupported
+                .iter()
+                .any(|skipped| skipped.provider == "exact")
+        );
+    }
+}
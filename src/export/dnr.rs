@@ -0,0 +1,869 @@
+//! Chrome `declarativeNetRequest` rules, for Manifest V3 extensions that can't block in `webRequest`.
+//!
+//! - Parameter rules become `queryTransform.removeParams` redirects, as long as they only match a finite set
+//!   of names (literals, `(?:a|b)` groups and `?`)
+//! - Complete providers become `block` rules, when `domain_blocking` is on
+//! - `blacklisted_domains`, `skip_localhost` and complete providers' exceptions become `allow` rules
+//! - Other exceptions only turn off their own provider, so they become a redirect removing what the other
+//!   providers still remove on their URLs, when those providers are limited to whole domains
+//!
+//! Only one redirect runs per request, so the parameters of catch-all providers (urlPattern `.*`, like
+//! `globalRules`) are folded into every other provider's rule. Redirections, raw rules and value rules aren't
+//! exported; referral marketing isn't applied by `UrlCleaner::clean_url` either. A `regexSubstitution` can't
+//! percent-decode what it captured, so redirections would send the browser to an encoded target.
+
+use anyhow::Result;
+use regex_syntax::{
+    ParserBuilder,
+    ast::{
+        self, AssertionKind, Ast, ClassSetBinaryOp, ClassSetItem, Flag, FlagsItemKind, GroupKind,
+        RepetitionKind, RepetitionRange,
+    },
+    hir::{Class, Hir, HirKind},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    PatternDomain, dynamic_options, from_options, host_domains, param_names, push_unique, skipped,
+    skipped_value, under,
+};
+use crate::{
+    CleaningOptions, Provider, Rule, RuleSet, SkippedRule,
+    js_regex::{self, Usage},
+    neat_url::NeatRule,
+};
+
+/// Chrome's guaranteed minimum of enabled static rules
+pub const MAX_RULES: usize = 30_000;
+/// Chrome's limit on rules using `regexFilter`
+pub const MAX_REGEX_RULES: usize = 1_000;
+/// Chrome's limit on the memory RE2 may use for a single `regexFilter`, in bytes
+pub const MAX_REGEX_MEMORY: usize = 2 * 1024;
+
+/// What an RE2 instruction is estimated to cost towards `MAX_REGEX_MEMORY`
+const INSTRUCTION_SIZE: usize = 16;
+
+/// RE2's limit on counted repetitions
+const MAX_REPEAT: u32 = 1_000;
+
+/// Every resource type the `webRequest` extension intercepts
+const RESOURCE_TYPES: &[&str] = &[
+    "main_frame",
+    "sub_frame",
+    "stylesheet",
+    "script",
+    "image",
+    "font",
+    "object",
+    "xmlhttprequest",
+    "ping",
+    "csp_report",
+    "media",
+    "websocket",
+    "other",
+];
+
+// Higher priorities win: site providers beat the catch-all ones, a provider's exceptions beat the cleaning
+// rules, and the options and complete providers' exceptions beat everything
+const PRIORITY_GLOBAL: u32 = 1;
+const PRIORITY_PROVIDER: u32 = 2;
+const PRIORITY_EXCEPTION: u32 = 3;
+const PRIORITY_BLOCK: u32 = 4;
+const PRIORITY_ALLOW: u32 = 5;
+
+/// A `declarativeNetRequest` rule, serialized the way the API expects it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DnrRule {
+    pub id: u32,
+    pub priority: u32,
+    pub action: Action,
+    pub condition: Condition,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Action {
+    #[serde(rename = "type")]
+    pub kind: ActionType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redirect: Option<Redirect>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ActionType {
+    Allow,
+    Block,
+    Redirect,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Redirect {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regex_substitution: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<Transform>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transform {
+    pub query_transform: QueryTransform,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueryTransform {
+    pub remove_params: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Condition {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub regex_filter: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub request_domains: Vec<String>,
+    pub resource_types: Vec<String>,
+}
+
+/// How many rules the output may have, and how large their regexes may get
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    pub max_rules: usize,
+    pub max_regex_rules: usize,
+    /// Estimated from the number of instructions RE2 compiles a `regexFilter` to
+    pub max_regex_memory: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_rules: MAX_RULES,
+            max_regex_rules: MAX_REGEX_RULES,
+            max_regex_memory: MAX_REGEX_MEMORY,
+        }
+    }
+}
+
+/// The exported rules, along with every pattern that couldn't be expressed
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DnrExport {
+    pub rules: Vec<DnrRule>,
+    pub unsupported: Vec<SkippedRule>,
+}
+
+/// A rule waiting for its id, with what it was made from in case it goes over the limits
+struct Pending {
+    priority: u32,
+    action: Action,
+    condition: Condition,
+    origin: SkippedRule,
+}
+
+/// Compile a ruleset into `declarativeNetRequest` rules, cleaning the way `UrlCleaner` would with `options`
+pub fn export(rules: &RuleSet, options: &CleaningOptions, limits: &Limits) -> Result<DnrExport> {
+    let mut unsupported = Vec::new();
+    let mut allows = Vec::new();
+    let mut blocks = Vec::new();
+    let mut exceptions = Vec::new();
+    let mut transforms = Vec::new();
+
+    unsupported.extend(dynamic_options(options));
+    if options.skip_localhost {
+        allows.push(Pending {
+            priority: PRIORITY_ALLOW,
+            action: action(ActionType::Allow, None),
+            condition: regex_condition(
+                r"^https?://(?:localhost(?:[:/]|$)|127\.|192\.168\.|10\.|172\.)",
+            ),
+            origin: from_options("skip_localhost", "localhost"),
+        });
+    }
+
+    if !options.blacklisted_domains.is_empty() {
+        allows.push(Pending {
+            priority: PRIORITY_ALLOW,
+            action: action(ActionType::Allow, None),
+            condition: Condition {
+                regex_filter: None,
+                request_domains: options.blacklisted_domains.clone(),
+                resource_types: resource_types(),
+            },
+            origin: from_options(
+                "blacklisted_domains",
+                &options.blacklisted_domains.join(","),
+            ),
+        });
+    }
+
+    // Plain parameter names from the options apply everywhere, like the catch-all providers
+    let mut option_params = Vec::new();
+    for rule in &options.additional_blocked_params {
+        let neat = NeatRule::parse(rule)?;
+        match neat.global_param() {
+            Some(name) => push_unique(&mut option_params, name.to_string()),
+            None => unsupported.push(SkippedRule {
+                reason: "only plain parameter names without a domain can be exported".to_string(),
+                ..from_options("additional_blocked_params", rule)
+            }),
+        }
+    }
+
+    let mut provider_params = Vec::new();
+    for provider in rules.providers().iter().filter(|p| !p.complete_provider) {
+        let mut params = Vec::new();
+        for rule in &provider.rules {
+            match param_names(rule.as_str()) {
+                Some(names) => names
+                    .into_iter()
+                    .for_each(|name| push_unique(&mut params, name)),
+                None => unsupported.push(skipped(provider, "rules", rule, PARAM_REASON)),
+            }
+        }
+        provider_params.push((provider, params));
+    }
+
+    let mut global_params = option_params.clone();
+    for (_, params) in provider_params.iter().filter(|(p, _)| catch_all(p)) {
+        params
+            .iter()
+            .for_each(|name| push_unique(&mut global_params, name.clone()));
+    }
+
+    for provider in rules.providers() {
+        let Some(url_pattern) = url_filter(
+            provider,
+            "urlPattern",
+            &provider.url_pattern,
+            limits,
+            &mut unsupported,
+        ) else {
+            continue;
+        };
+
+        for exception in &provider.exceptions {
+            let Some(filter) =
+                url_filter(provider, "exceptions", exception, limits, &mut unsupported)
+            else {
+                continue;
+            };
+            let origin = skipped(provider, "exceptions", exception, "");
+
+            if provider.complete_provider {
+                allows.push(Pending {
+                    priority: PRIORITY_ALLOW,
+                    action: action(ActionType::Allow, None),
+                    condition: regex_condition(&filter),
+                    origin,
+                });
+                continue;
+            }
+
+            // Other providers still clean these URLs, so the exception removes what they would
+            let rules = exception_rules(
+                provider,
+                exception,
+                &filter,
+                &option_params,
+                &provider_params,
+            );
+            let rules = match rules {
+                Ok(rules) => rules,
+                Err(reason) => {
+                    unsupported.push(SkippedRule {
+                        reason: reason.to_string(),
+                        ..origin
+                    });
+                    continue;
+                }
+            };
+            for (action, condition) in rules {
+                exceptions.push(Pending {
+                    priority: PRIORITY_EXCEPTION,
+                    action,
+                    condition,
+                    origin: origin.clone(),
+                });
+            }
+        }
+
+        if provider.complete_provider {
+            if options.domain_blocking {
+                blocks.push(Pending {
+                    priority: PRIORITY_BLOCK,
+                    action: action(ActionType::Block, None),
+                    condition: regex_condition(&url_pattern),
+                    origin: skipped(provider, "urlPattern", &provider.url_pattern, ""),
+                });
+            }
+            continue;
+        }
+
+        for redirection in &provider.redirections {
+            unsupported.push(skipped(
+                provider,
+                "redirections",
+                redirection,
+                "regexSubstitution can't percent-decode the captured target",
+            ));
+        }
+
+        for raw_rule in &provider.raw_rules {
+            unsupported.push(skipped(
+                provider,
+                "rawRules",
+                raw_rule,
+                "raw rules rewrite the URL, which only a redirect to a fixed substitution can do",
+            ));
+        }
+        for value_rule in &provider.value_rules {
+            unsupported.push(skipped_value(
+                provider,
+                value_rule,
+                "removeParams can't look at parameter values",
+            ));
+        }
+
+        if catch_all(provider) {
+            continue;
+        }
+
+        let mut params = provider_params
+            .iter()
+            .find(|(p, _)| p.name == provider.name)
+            .map(|(_, params)| params.clone())
+            .unwrap_or_default();
+        if !params.is_empty() {
+            global_params
+                .iter()
+                .for_each(|name| push_unique(&mut params, name.clone()));
+            transforms.push(Pending {
+                priority: PRIORITY_PROVIDER,
+                action: remove_params(params),
+                condition: regex_condition(&url_pattern),
+                origin: skipped(provider, "rules", &provider.url_pattern, ""),
+            });
+        }
+    }
+
+    if !global_params.is_empty() {
+        transforms.push(Pending {
+            priority: PRIORITY_GLOBAL,
+            action: remove_params(global_params),
+            condition: Condition {
+                regex_filter: None,
+                request_domains: Vec::new(),
+                resource_types: resource_types(),
+            },
+            origin: from_options("rules", "*"),
+        });
+    }
+
+    // Exceptions go first, so it's the cleaning that gets cut when there are too many rules
+    let mut output = Vec::new();
+    let mut regex_rules = 0;
+    for pending in allows
+        .into_iter()
+        .chain(blocks)
+        .chain(exceptions)
+        .chain(transforms)
+    {
+        let is_regex = pending.condition.regex_filter.is_some();
+        let reason = if output.len() >= limits.max_rules {
+            Some(format!("over the limit of {} rules", limits.max_rules))
+        } else if is_regex && regex_rules >= limits.max_regex_rules {
+            Some(format!(
+                "over the limit of {} regex rules",
+                limits.max_regex_rules
+            ))
+        } else {
+            None
+        };
+
+        if let Some(reason) = reason {
+            unsupported.push(SkippedRule {
+                reason,
+                ..pending.origin
+            });
+            continue;
+        }
+
+        regex_rules += usize::from(is_regex);
+        output.push(DnrRule {
+            id: output.len() as u32 + 1,
+            priority: pending.priority,
+            action: pending.action,
+            condition: pending.condition,
+        });
+    }
+
+    Ok(DnrExport {
+        rules: output,
+        unsupported,
+    })
+}
+
+fn catch_all(provider: &Provider) -> bool {
+    provider.url_pattern.as_str() == ".*"
+}
+
+/// Rules removing what the other providers and the options still remove on the URLs an exception of
+/// `provider` matches, split by `requestDomains` where that differs between its domains, or why the other
+/// providers can't be told apart
+fn exception_rules(
+    provider: &Provider,
+    exception: &Rule,
+    filter: &str,
+    option_params: &[String],
+    provider_params: &[(&Provider, Vec<String>)],
+) -> Result<Vec<(Action, Condition)>, &'static str> {
+    let (domains, _) = host_domains(exception.as_str()).map_err(|_| {
+        "the exception's host isn't a list of names, so the providers that still apply are unknown"
+    })?;
+
+    let mut groups: Vec<(Vec<&PatternDomain>, Vec<String>)> = Vec::new();
+    for domain in &domains {
+        let mut removed = Vec::new();
+        for (other, params) in provider_params {
+            if other.name == provider.name || params.is_empty() {
+                continue;
+            }
+            if !catch_all(other) {
+                let (other_domains, whole_host) = host_domains(other.url_pattern.as_str())
+                    .map_err(|_| "another provider's urlPattern isn't a list of domains")?;
+                if !other_domains.iter().any(|d| domain.overlaps(d)) {
+                    continue;
+                }
+                if !whole_host || !other_domains.iter().any(|d| domain.within(d)) {
+                    return Err("another provider only applies to some of the exception's URLs");
+                }
+            }
+            params
+                .iter()
+                .for_each(|name| push_unique(&mut removed, name.clone()));
+        }
+        option_params
+            .iter()
+            .for_each(|name| push_unique(&mut removed, name.clone()));
+
+        match groups.iter_mut().find(|(_, params)| *params == removed) {
+            Some((group, _)) => group.push(domain),
+            None => groups.push((vec![domain], removed)),
+        }
+    }
+
+    let rule = |request_domains, params: Vec<String>| {
+        let action = if params.is_empty() {
+            action(ActionType::Allow, None)
+        } else {
+            remove_params(params)
+        };
+        let condition = Condition {
+            request_domains,
+            ..regex_condition(filter)
+        };
+        (action, condition)
+    };
+
+    if groups.len() == 1 {
+        return Ok(groups
+            .into_iter()
+            .map(|(_, params)| rule(Vec::new(), params))
+            .collect());
+    }
+
+    // requestDomains always takes in subdomains, and can't leave the top level domain open
+    let separable = domains.iter().all(|domain| {
+        !domain.any_tld
+            && domains
+                .iter()
+                .all(|other| other == domain || !under(&other.name, &domain.name, true))
+    });
+    if !separable {
+        return Err(
+            "other providers apply to some of the exception's domains, which requestDomains can't tell apart",
+        );
+    }
+    Ok(groups
+        .into_iter()
+        .map(|(group, params)| {
+            let names = group.iter().map(|domain| domain.name.clone()).collect();
+            rule(names, params)
+        })
+        .collect())
+}
+
+const PARAM_REASON: &str =
+    "matches an open-ended set of parameter names, removeParams needs literal names";
+
+/// A URL pattern in RE2 syntax within Chrome's limits, or why it can't be one
+fn url_filter(
+    provider: &Provider,
+    field: &str,
+    rule: &Rule,
+    limits: &Limits,
+    unsupported: &mut Vec<SkippedRule>,
+) -> Option<String> {
+    let translated = js_regex::translate(rule.as_str(), Usage::Url);
+    let construct = translated
+        .unsupported
+        .or_else(|| re2_unsupported(&translated.pattern));
+    if let Some(construct) = construct {
+        let reason = format!("{} is not supported by Chrome's RE2", construct);
+        unsupported.push(skipped(provider, field, rule, &reason));
+        return None;
+    }
+
+    let memory = regex_memory(&translated.pattern);
+    if memory > limits.max_regex_memory {
+        let reason = format!(
+            "needs about {} bytes compiled, over the limit of {}",
+            memory, limits.max_regex_memory
+        );
+        unsupported.push(skipped(provider, field, rule, &reason));
+        return None;
+    }
+
+    Some(translated.pattern)
+}
+
+/// What RE2 can't parse in a pattern the `regex` crate accepts
+fn re2_unsupported(pattern: &str) -> Option<&'static str> {
+    struct Re2Syntax;
+
+    impl ast::Visitor for Re2Syntax {
+        type Output = ();
+        type Err = &'static str;
+
+        fn finish(self) -> Result<(), Self::Err> {
+            Ok(())
+        }
+
+        fn visit_pre(&mut self, ast: &Ast) -> Result<(), Self::Err> {
+            let flags = match ast {
+                Ast::Assertion(assertion) => {
+                    return match assertion.kind {
+                        AssertionKind::StartLine
+                        | AssertionKind::EndLine
+                        | AssertionKind::StartText
+                        | AssertionKind::EndText
+                        | AssertionKind::WordBoundary
+                        | AssertionKind::NotWordBoundary => Ok(()),
+                        _ => Err("a start or end of word assertion"),
+                    };
+                }
+                Ast::Repetition(repetition) => {
+                    let count = match repetition.op.kind {
+                        RepetitionKind::Range(RepetitionRange::Exactly(n))
+                        | RepetitionKind::Range(RepetitionRange::AtLeast(n)) => n,
+                        RepetitionKind::Range(RepetitionRange::Bounded(_, n)) => n,
+                        _ => 0,
+                    };
+                    return if count > MAX_REPEAT {
+                        Err("a repetition over 1000")
+                    } else {
+                        Ok(())
+                    };
+                }
+                Ast::Flags(set) => &set.flags,
+                Ast::Group(group) => match &group.kind {
+                    GroupKind::NonCapturing(flags) => flags,
+                    _ => return Ok(()),
+                },
+                _ => return Ok(()),
+            };
+
+            let unsupported = flags.items.iter().any(|item| {
+                matches!(
+                    item.kind,
+                    FlagsItemKind::Flag(Flag::IgnoreWhitespace | Flag::CRLF | Flag::Unicode)
+                )
+            });
+            if unsupported {
+                Err("the x, R or u flag")
+            } else {
+                Ok(())
+            }
+        }
+
+        fn visit_class_set_item_pre(&mut self, item: &ClassSetItem) -> Result<(), Self::Err> {
+            match item {
+                ClassSetItem::Bracketed(_) => Err("a nested character class"),
+                _ => Ok(()),
+            }
+        }
+
+        fn visit_class_set_binary_op_pre(
+            &mut self,
+            _op: &ClassSetBinaryOp,
+        ) -> Result<(), Self::Err> {
+            Err("a character class operation")
+        }
+    }
+
+    let ast = ast::parse::Parser::new().parse(pattern).ok()?;
+    ast::visit(&ast, Re2Syntax).err()
+}
+
+/// Roughly how much memory RE2 needs for `pattern`, from the instructions it compiles to
+fn regex_memory(pattern: &str) -> usize {
+    // RE2's classes like `\w` are ASCII only, and `.` is counted as one byte range
+    let hir = ParserBuilder::new()
+        .unicode(false)
+        .utf8(false)
+        .build()
+        .parse(pattern);
+    hir.map_or(0, |hir| instructions(&hir) * INSTRUCTION_SIZE)
+}
+
+fn instructions(hir: &Hir) -> usize {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => 1,
+        HirKind::Literal(literal) => literal.0.len(),
+        // A byte range per range and an alternation between them
+        HirKind::Class(Class::Bytes(class)) => class.ranges().len() * 2 - 1,
+        HirKind::Class(Class::Unicode(class)) => class.ranges().len() * 2 - 1,
+        HirKind::Repetition(repetition) => {
+            let sub = instructions(&repetition.sub);
+            let min = repetition.min as usize;
+            match repetition.max {
+                Some(max) => min * sub + (max as usize - min) * (sub + 1),
+                None => min.max(1) * sub + 1,
+            }
+        }
+        // Chrome compiles without capturing unless it needs a substitution
+        HirKind::Capture(capture) => instructions(&capture.sub),
+        HirKind::Concat(subs) => subs.iter().map(instructions).sum(),
+        HirKind::Alternation(subs) => subs.iter().map(instructions).sum::<usize>() + subs.len() - 1,
+    }
+}
+
+fn resource_types() -> Vec<String> {
+    RESOURCE_TYPES.iter().map(|kind| kind.to_string()).collect()
+}
+
+fn regex_condition(filter: &str) -> Condition {
+    Condition {
+        regex_filter: Some(filter.to_string()),
+        request_domains: Vec::new(),
+        resource_types: resource_types(),
+    }
+}
+
+fn action(kind: ActionType, redirect: Option<Redirect>) -> Action {
+    Action { kind, redirect }
+}
+
+fn remove_params(params: Vec<String>) -> Action {
+    action(
+        ActionType::Redirect,
+        Some(Redirect {
+            regex_substitution: None,
+            transform: Some(Transform {
+                query_transform: QueryTransform {
+                    remove_params: params,
+                },
+            }),
+        }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{RuleFile, RuleSource};
+
+    #[test]
+    fn test_export_dnr() {
+        let file = RuleFile::from_json(
+            r#"{"providers": {
+                "globalRules": {"urlPattern": ".*", "rules": ["(?:%3F)?utm_source", "ga_[a-z_]+"]},
+                "shop": {
+                    "domains": ["shop.test"],
+                    "rules": ["tag"],
+                    "exceptions": ["^https?:\\/\\/shop\\.test\\/checkout"],
+                    "redirections": ["^https?:\\/\\/shop\\.test\\/out\\?to=([^&]+)"],
+                    "rawRules": ["\\/ref=[^/?]*"]
+                },
+                "ads": {"domains": ["ads.test"], "completeProvider": true}
+            }}"#,
+        )
+        .unwrap();
+        let rules = RuleSet::from_file(&file, RuleSource::User("test.json".into())).unwrap();
+
+        let options = CleaningOptions {
+            additional_blocked_params: vec!["mc_eid".to_string(), "ref@*.shop.test".to_string()],
+            ..Default::default()
+        };
+        let export = export(&rules, &options, &Limits::default()).unwrap();
+
+        let kinds: Vec<(ActionType, u32)> = export
+            .rules
+            .iter()
+            .map(|rule| (rule.action.kind, rule.priority))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                (ActionType::Allow, PRIORITY_ALLOW),
+                (ActionType::Block, PRIORITY_BLOCK),
+                (ActionType::Redirect, PRIORITY_EXCEPTION),
+                (ActionType::Redirect, PRIORITY_PROVIDER),
+                (ActionType::Redirect, PRIORITY_GLOBAL),
+            ]
+        );
+
+        let removed = |rule: &DnrRule| {
+            rule.action
+                .redirect
+                .as_ref()
+                .unwrap()
+                .transform
+                .as_ref()
+                .unwrap()
+                .query_transform
+                .remove_params
+                .clone()
+        };
+        assert_eq!(removed(&export.rules[2]), vec!["utm_source", "mc_eid"]);
+        assert_eq!(
+            removed(&export.rules[3]),
+            vec!["tag", "mc_eid", "utm_source"]
+        );
+        assert_eq!(removed(&export.rules[4]), vec!["mc_eid", "utm_source"]);
+
+        let fields: Vec<&str> = export
+            .unsupported
+            .iter()
+            .map(|s| s.field.as_str())
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "additional_blocked_params",
+                "rules",
+                "redirections",
+                "rawRules"
+            ]
+        );
+
+        let limits = Limits {
+            max_regex_rules: 2,
+            ..Limits::default()
+        };
+        let export = super::export(&rules, &CleaningOptions::default(), &limits).unwrap();
+        assert_eq!(export.rules.len(), 3);
+        assert!(
+            export
+                .unsupported
+                .iter()
+                .any(|s| s.reason.contains("2 regex rules"))
+        );
+    }
+
+    #[test]
+    fn test_exceptions_keep_other_providers() {
+        let file = RuleFile::from_json(
+            r#"{"providers": {
+                "globalRules": {
+                    "urlPattern": ".*",
+                    "rules": ["utm_source"],
+                    "exceptions": [
+                        "^https?:\\/\\/(?:[a-z0-9-]+\\.)*?(?:video|other)\\.test\\/embed",
+                        "^https?:\\/\\/(?:[a-z0-9-]+\\.)*?maps\\.test",
+                        "^https?:\\/\\/[^/]+\\/login"
+                    ]
+                },
+                "video": {"domains": ["video.test"], "rules": ["si", "pp"]},
+                "maps": {"urlPattern": "^https?:\\/\\/maps\\.test", "rules": ["entry"]},
+                "local": {"domains": ["local.test"], "rules": ["ref"], "exceptions": ["^https?:\\/\\/local\\.test\\/"]}
+            }}"#,
+        )
+        .unwrap();
+        let rules = RuleSet::from_file(&file, RuleSource::User("test.json".into())).unwrap();
+        let options = CleaningOptions {
+            skip_localhost: false,
+            ..Default::default()
+        };
+        let export = export(&rules, &options, &Limits::default()).unwrap();
+
+        // Each exception still removes what the other providers would, per domain where that differs
+        let exceptions: Vec<(Vec<String>, Vec<String>)> = export
+            .rules
+            .iter()
+            .filter(|rule| rule.priority == PRIORITY_EXCEPTION)
+            .map(|rule| {
+                let params = rule.action.redirect.as_ref().map(|redirect| {
+                    let transform = redirect.transform.as_ref().unwrap();
+                    transform.query_transform.remove_params.clone()
+                });
+                (
+                    rule.condition.request_domains.clone(),
+                    params.unwrap_or_default(),
+                )
+            })
+            .collect();
+        let strings = |values: &[&str]| values.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            exceptions,
+            vec![
+                (strings(&["video.test"]), strings(&["si", "pp"])),
+                (strings(&["other.test"]), Vec::new()),
+                (Vec::new(), strings(&["utm_source"])),
+            ]
+        );
+        assert!(
+            export
+                .rules
+                .iter()
+                .all(|rule| rule.priority != PRIORITY_ALLOW)
+        );
+
+        let unsupported: Vec<&str> = export
+            .unsupported
+            .iter()
+            .filter(|s| s.field == "exceptions")
+            .map(|s| s.reason.as_str())
+            .collect();
+        assert_eq!(
+            unsupported,
+            vec![
+                "another provider only applies to some of the exception's URLs",
+                "the exception's host isn't a list of names, so the providers that still apply are unknown",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_regex_limits() {
+        assert_eq!(re2_unsupported(r"^https?://(?:www\.)?shop\.test/"), None);
+        assert_eq!(
+            re2_unsupported(r"[a-z--q]"),
+            Some("a character class operation")
+        );
+        assert_eq!(
+            re2_unsupported(r"\<ref"),
+            Some("a start or end of word assertion")
+        );
+        assert_eq!(re2_unsupported(r"a{1001}"), Some("a repetition over 1000"));
+
+        let file = RuleFile::from_json(
+            r#"{"providers": {
+                "small": {"urlPattern": "^https?:\\/\\/(?:[a-z0-9-]+\\.)*?small(?:\\.[a-z]{2,}){1,}", "rules": ["ref"]},
+                "large": {"urlPattern": "^https?:\\/\\/large\\.test\\/[a-z0-9]{1,200}", "rules": ["ref"]}
+            }}"#,
+        )
+        .unwrap();
+        let rules = RuleSet::from_file(&file, RuleSource::BuiltIn).unwrap();
+        let export = export(&rules, &CleaningOptions::default(), &Limits::default()).unwrap();
+
+        let unsupported: Vec<&str> = export
+            .unsupported
+            .iter()
+            .filter(|s| s.reason.contains("bytes compiled"))
+            .map(|s| s.provider.as_str())
+            .collect();
+        assert_eq!(unsupported, vec!["large"]);
+    }
+}
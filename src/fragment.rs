@@ -0,0 +1,106 @@
+//! Parameters hiding in URL fragments: hash-routed query strings (`#/page?utm_source=x`), key/value fragments
+//! (`#xtor=RSS-1`) and text directives (`#:~:text=`).
+//!
+//! Anything else is a plain anchor, like `#section-2`, and is never touched.
+
+use std::borrow::Cow;
+
+/// Starts the fragment directive, which is for the browser (`text=` highlighting) rather than the page
+const DIRECTIVE_DELIMITER: &str = ":~:";
+
+/// A fragment carrying parameters
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Fragment<'a> {
+    /// What comes before the parameters: the route and its `?`, or nothing
+    prefix: &'a str,
+    /// The `&` separated parameters, as written
+    pub(crate) params: Vec<&'a str>,
+    /// The directive, with its delimiter, or nothing
+    directive: &'a str,
+}
+
+impl<'a> Fragment<'a> {
+    /// Parse a fragment (without the `#`), if it has parameters
+    pub(crate) fn parse(fragment: &'a str) -> Option<Self> {
+        let (main, directive) = split_directive(fragment);
+
+        let (prefix, query) = match main.split_once('?') {
+            // Hash routing, `#/page?utm_source=x` or `#!/page?utm_source=x`
+            Some((route, query)) => (&main[..route.len() + 1], query),
+            None if is_key_value(main) => ("", main),
+            None => return None,
+        };
+
+        Some(Self {
+            prefix,
+            params: query.split('&').filter(|param| !param.is_empty()).collect(),
+            directive,
+        })
+    }
+
+    /// The fragment with only the `kept` parameters, dropping the `?` of a route left without any
+    pub(crate) fn rebuild(&self, kept: &[&str]) -> String {
+        let main = if kept.is_empty() {
+            self.prefix.trim_end_matches('?').to_string()
+        } else {
+            format!("{}{}", self.prefix, kept.join("&"))
+        };
+        format!("{}{}", main, self.directive)
+    }
+}
+
+/// The decoded key and value of a `key=value` parameter
+pub(crate) fn pair(param: &str) -> (Cow<'_, str>, Cow<'_, str>) {
+    url::form_urlencoded::parse(param.as_bytes())
+        .next()
+        .unwrap_or_default()
+}
+
+/// The fragment without its directive, if it has one
+pub(crate) fn without_directive(fragment: &str) -> Option<&str> {
+    let (main, directive) = split_directive(fragment);
+    (!directive.is_empty()).then_some(main)
+}
+
+fn split_directive(fragment: &str) -> (&str, &str) {
+    match fragment.find(DIRECTIVE_DELIMITER) {
+        Some(start) => fragment.split_at(start),
+        None => (fragment, ""),
+    }
+}
+
+/// Whether a fragment is made of `key=value` pairs, rather than being an anchor or a route
+fn is_key_value(fragment: &str) -> bool {
+    !fragment.is_empty()
+        && fragment.split('&').all(|param| {
+            param
+                .split_once('=')
+                .is_some_and(|(key, _)| !key.is_empty() && !key.contains(['/', ' ', '#']))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fragments() {
+        let route = Fragment::parse("/page?id=1&utm_source=x").unwrap();
+        assert_eq!(route.params, vec!["id=1", "utm_source=x"]);
+        assert_eq!(route.rebuild(&["id=1"]), "/page?id=1");
+        assert_eq!(route.rebuild(&[]), "/page");
+
+        let pairs = Fragment::parse("xtor=RSS-1:~:text=hello").unwrap();
+        assert_eq!(pairs.params, vec!["xtor=RSS-1"]);
+        assert_eq!(pairs.rebuild(&[]), ":~:text=hello");
+
+        assert_eq!(Fragment::parse("section-2"), None);
+        assert_eq!(Fragment::parse("/docs/intro"), None);
+        assert_eq!(Fragment::parse(""), None);
+
+        assert_eq!(pair("utm%5Fsource=x%20y").0, "utm_source");
+        assert_eq!(pair("utm%5Fsource=x%20y").1, "x y");
+        assert_eq!(without_directive("top:~:text=a"), Some("top"));
+        assert_eq!(without_directive("top"), None);
+    }
+}
@@ -0,0 +1,159 @@
+//! Scoring of query parameters the rules don't know about, to find trackers that aren't in ClearURLs yet.
+//!
+//! A parameter scores points for a tracker-like name (`utm_`, `_hs`, `mc_`, `trk`, `*clid`) and for a value
+//! that looks like an identifier rather than something a person typed: long, high entropy, a UUID or
+//! base64/hex blob. Scores go from 0 to 1.
+
+/// Points for each signal, out of 100
+const NAME_POINTS: u32 = 45;
+const LONG_POINTS: u32 = 15;
+const ENTROPY_POINTS: u32 = 20;
+const UUID_POINTS: u32 = 25;
+const ENCODED_POINTS: u32 = 20;
+
+/// Parameters scoring less than this aren't worth reporting
+pub(crate) const SUSPICIOUS: f64 = 0.3;
+
+/// Values at least this long count as long
+const LONG_VALUE: usize = 20;
+
+/// Bits per character above which a value looks random
+const HIGH_ENTROPY: f64 = 3.5;
+
+/// Name prefixes of common tracking and analytics parameters
+const TRACKER_PREFIXES: &[&str] = &[
+    "utm_", "_hs", "__hs", "hs_", "mc_", "trk", "track", "_ga", "ga_", "_gl", "fb_", "mkt_", "cmp",
+    "campaign", "aff", "click", "ref_", "oly_", "vero_", "_branch", "pk_", "mtm_", "piwik_",
+    "matomo_", "_kx", "igsh",
+];
+
+/// Name suffixes of click identifiers and campaign fields
+const TRACKER_SUFFIXES: &[&str] = &[
+    "clid",
+    "click_id",
+    "_source",
+    "_medium",
+    "_campaign",
+    "_tracking",
+];
+
+/// How suspicious a parameter looks, and why
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Score {
+    pub(crate) score: f64,
+    pub(crate) reasons: Vec<&'static str>,
+}
+
+/// Score a parameter by its (decoded) key and value
+pub(crate) fn score(key: &str, value: &str) -> Score {
+    let mut points = 0;
+    let mut reasons = Vec::new();
+
+    let name = key.to_ascii_lowercase();
+    if TRACKER_PREFIXES
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+        || TRACKER_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+    {
+        points += NAME_POINTS;
+        reasons.push("tracker-like name");
+    }
+
+    // Only single tokens can be identifiers, anything with spaces was probably typed
+    if is_token(value) {
+        if value.len() >= LONG_VALUE {
+            points += LONG_POINTS;
+            reasons.push("long value");
+        }
+        if value.len() >= 12 && entropy(value) >= HIGH_ENTROPY {
+            points += ENTROPY_POINTS;
+            reasons.push("high entropy value");
+        }
+
+        if is_uuid(value) {
+            points += UUID_POINTS;
+            reasons.push("UUID value");
+        } else if value.len() >= 16 && is_hex(value) {
+            points += ENCODED_POINTS;
+            reasons.push("hex value");
+        } else if value.len() >= 16 && is_base64(value) {
+            points += ENCODED_POINTS;
+            reasons.push("base64 value");
+        }
+    }
+
+    Score {
+        score: f64::from(points.min(100)) / 100.0,
+        reasons,
+    }
+}
+
+fn is_token(value: &str) -> bool {
+    !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_.~+/=%".contains(c))
+}
+
+/// Shannon entropy in bits per byte
+pub(crate) fn entropy(value: &str) -> f64 {
+    let mut counts = [0usize; 256];
+    for byte in value.bytes() {
+        counts[byte as usize] += 1;
+    }
+
+    let len = value.len() as f64;
+    counts
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn is_uuid(value: &str) -> bool {
+    let groups: Vec<&str> = value.split('-').collect();
+    groups.iter().map(|group| group.len()).eq([8, 4, 4, 4, 12])
+        && groups.iter().all(|group| is_hex(group))
+}
+
+fn is_hex(value: &str) -> bool {
+    value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Base64 or base64url, mixing cases and digits the way encoded bytes do
+fn is_base64(value: &str) -> bool {
+    let body = value.trim_end_matches('=');
+    value.len() - body.len() <= 2
+        && body
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "+/-_".contains(c))
+        && body.chars().any(|c| c.is_ascii_uppercase())
+        && body.chars().any(|c| c.is_ascii_lowercase())
+        && body.chars().any(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scores() {
+        let tracker = score("trk_campaign", "spring");
+        assert_eq!(tracker.score, 0.45);
+        assert_eq!(tracker.reasons, vec!["tracker-like name"]);
+
+        let uuid = score("sid", "3f2b8c1e-9d4a-4e6f-8b7c-2a1d0e9f4b3c");
+        assert_eq!(uuid.score, 0.6);
+        assert!(uuid.reasons.contains(&"UUID value"));
+
+        let blob = score("_hsenc", "p2ANqtz-8Fh3kQx9LmZr4VwYbN0cTgHd");
+        assert_eq!(blob.score, 1.0);
+
+        assert_eq!(score("q", "how to cook pasta quickly at home").score, 0.0);
+        assert_eq!(score("page", "2").score, 0.0);
+        assert!(score("id", "12345").score < SUSPICIOUS);
+    }
+}
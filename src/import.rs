@@ -0,0 +1,149 @@
+//! Converting other query-stripping list formats into plink providers
+
+use std::{collections::BTreeMap, fmt, fs, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{ProviderSpec, RuleFile};
+
+pub mod adblock;
+pub mod brave;
+pub mod firefox;
+
+/// A list format that can be imported
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// uBlock Origin / AdGuard filters using `$removeparam`
+    Adblock,
+    /// Firefox's `privacy.query_stripping` prefs or remote-settings `query-stripping` records
+    Firefox,
+    /// Brave's query filter list
+    Brave,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Adblock => "adblock",
+            Format::Firefox => "firefox",
+            Format::Brave => "brave",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A line (or entry) of the source list that couldn't be converted
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Unsupported {
+    /// 1-based line number, 0 for entries of formats without lines like JSON
+    pub line: usize,
+    pub text: String,
+    pub reason: String,
+}
+
+/// The providers converted from a list, along with everything that was left out
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Import {
+    pub file: RuleFile,
+    pub unsupported: Vec<Unsupported>,
+}
+
+/// Convert a list in the given format
+pub fn import(contents: &str, format: Format) -> Result<Import> {
+    match format {
+        Format::Adblock => Ok(adblock::import(contents)),
+        Format::Firefox => firefox::import(contents),
+        Format::Brave => brave::import(contents),
+    }
+}
+
+/// Read and convert a list from disk
+pub fn import_path(path: &Path, format: Format) -> Result<Import> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    import(&contents, format).with_context(|| format!("Failed to import {}", path.display()))
+}
+
+/// Providers being assembled by an importer, named `<prefix>` for the global one and `<prefix>:<domain>`
+/// for the rest, so they never collide with the ClearURLs providers they're merged next to
+struct Providers {
+    prefix: &'static str,
+    providers: BTreeMap<String, ProviderSpec>,
+}
+
+impl Providers {
+    fn new(prefix: &'static str) -> Self {
+        Self {
+            prefix,
+            providers: BTreeMap::new(),
+        }
+    }
+
+    /// The provider that applies to every URL
+    fn global(&mut self) -> &mut ProviderSpec {
+        self.providers
+            .entry(self.prefix.to_string())
+            .or_insert_with(|| ProviderSpec {
+                url_pattern: Some(".*".to_string()),
+                ..Default::default()
+            })
+    }
+
+    /// The provider for one domain and its subdomains
+    fn domain(&mut self, domain: &str) -> &mut ProviderSpec {
+        self.providers
+            .entry(format!("{}:{}", self.prefix, domain))
+            .or_insert_with(|| ProviderSpec {
+                domains: vec![domain.to_string()],
+                ..Default::default()
+            })
+    }
+
+    /// A provider for every URL except those on the given domains
+    fn excluding(&mut self, domains: &[&str]) -> &mut ProviderSpec {
+        let name = domains
+            .iter()
+            .map(|domain| format!("~{}", domain))
+            .collect::<Vec<_>>()
+            .join("|");
+
+        self.providers
+            .entry(format!("{}:{}", self.prefix, name))
+            .or_insert_with(|| ProviderSpec {
+                url_pattern: Some(".*".to_string()),
+                exceptions: domains
+                    .iter()
+                    .map(|domain| domain_pattern(domain))
+                    .collect(),
+                ..Default::default()
+            })
+    }
+
+    fn into_file(self) -> RuleFile {
+        RuleFile {
+            providers: self.providers,
+        }
+    }
+}
+
+/// A URL pattern matching a domain and its subdomains
+fn domain_pattern(domain: &str) -> String {
+    ProviderSpec {
+        domains: vec![domain.to_string()],
+        ..Default::default()
+    }
+    .url_pattern()
+    .expect("A provider with domains has a urlPattern")
+}
+
+/// Whether a string looks like a host name (optionally with a `*` TLD), rather than a URL pattern
+fn is_domain(domain: &str) -> bool {
+    let domain = domain.strip_suffix(".*").unwrap_or(domain);
+    !domain.is_empty()
+        && domain.contains('.')
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+}
@@ -0,0 +1,331 @@
+//! uBlock Origin / AdGuard `$removeparam` filters.
+//!
+//! Supported are plain parameter names, `/regex/` names, `||domain^` and `domain=` scoping (including `~`
+//! exclusions) and `@@` exceptions that turn parameter removal off for a whole site.
+
+use super::{Import, Providers, Unsupported, domain_pattern, is_domain};
+use crate::ProviderSpec;
+
+/// Options that only narrow down which requests a filter applies to, which doesn't matter for links
+const IGNORED_OPTIONS: &[&str] = &[
+    "document",
+    "doc",
+    "all",
+    "important",
+    "third-party",
+    "~third-party",
+    "3p",
+    "~3p",
+    "first-party",
+    "~first-party",
+    "1p",
+    "~1p",
+];
+
+/// What a `$removeparam` filter removes
+enum Removal {
+    /// Every parameter
+    All,
+    Name(String),
+    /// A regex over the parameter name, in JavaScript syntax
+    Pattern(String),
+}
+
+/// A parsed filter line
+struct Filter {
+    exception: bool,
+    removal: Removal,
+    /// Domains it's limited to, empty for every domain
+    domains: Vec<String>,
+    /// Domains it's excluded from
+    excluded: Vec<String>,
+}
+
+/// Convert a filter list, reporting every line that isn't a supported `$removeparam` filter
+pub fn import(list: &str) -> Import {
+    let mut providers = Providers::new("adblock");
+    let mut unsupported = Vec::new();
+    let mut exceptions = Vec::new();
+
+    for (i, line) in list.lines().enumerate() {
+        let line = line.trim();
+
+        // Comments and the `[Adblock Plus 2.0]` style header
+        if line.is_empty()
+            || line.starts_with('!')
+            || (line.starts_with('[') && line.ends_with(']'))
+        {
+            continue;
+        }
+
+        let filter = match parse(line) {
+            Ok(filter) => filter,
+            Err(reason) => {
+                unsupported.push(Unsupported {
+                    line: i + 1,
+                    text: line.to_string(),
+                    reason,
+                });
+                continue;
+            }
+        };
+
+        if filter.exception {
+            // Parameter removal is off for the whole site, which is what a provider exception does
+            exceptions.extend(filter.domains);
+            continue;
+        }
+
+        let excluded: Vec<&str> = filter.excluded.iter().map(String::as_str).collect();
+        if filter.domains.is_empty() {
+            let provider = if excluded.is_empty() {
+                providers.global()
+            } else {
+                providers.excluding(&excluded)
+            };
+            add_removal(provider, &filter.removal);
+            continue;
+        }
+
+        for domain in &filter.domains {
+            let provider = providers.domain(domain);
+            for excluded in &excluded {
+                let pattern = domain_pattern(excluded);
+                if !provider.exceptions.contains(&pattern) {
+                    provider.exceptions.push(pattern);
+                }
+            }
+            add_removal(provider, &filter.removal);
+        }
+    }
+
+    let mut file = providers.into_file();
+    for domain in exceptions {
+        let pattern = domain_pattern(&domain);
+        for spec in file.providers.values_mut() {
+            if !spec.exceptions.contains(&pattern) {
+                spec.exceptions.push(pattern.clone());
+            }
+        }
+    }
+
+    Import { file, unsupported }
+}
+
+fn add_removal(provider: &mut ProviderSpec, removal: &Removal) {
+    let (list, rule) = match removal {
+        Removal::All => (&mut provider.rules, ".*".to_string()),
+        Removal::Name(name) => (&mut provider.params, name.clone()),
+        Removal::Pattern(pattern) => (&mut provider.rules, pattern.clone()),
+    };
+
+    if !list.contains(&rule) {
+        list.push(rule);
+    }
+}
+
+fn parse(line: &str) -> Result<Filter, String> {
+    if line.contains("##") || line.contains("#@#") || line.contains("#?#") || line.contains("#$#") {
+        return Err("cosmetic filter".to_string());
+    }
+
+    let (exception, line) = match line.strip_prefix("@@") {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+
+    let (pattern, options) = line
+        .split_once('$')
+        .ok_or_else(|| "not a $removeparam filter".to_string())?;
+
+    let options = split_options(options);
+    let is_removeparam = |option: &&str| {
+        let name = option.split_once('=').map_or(*option, |(name, _)| name);
+        name == "removeparam" || name == "queryprune"
+    };
+    if !options.iter().any(is_removeparam) {
+        return Err("not a $removeparam filter".to_string());
+    }
+
+    let mut removal = None;
+    let mut domains = Vec::new();
+    let mut excluded = Vec::new();
+
+    for option in options {
+        let (name, value) = match option.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (option, None),
+        };
+
+        match name {
+            "removeparam" | "queryprune" => removal = Some(parse_removal(value)?),
+            "domain" | "from" => {
+                for domain in value.unwrap_or_default().split('|') {
+                    match domain.strip_prefix('~') {
+                        Some(domain) if is_domain(domain) => excluded.push(domain.to_string()),
+                        None if is_domain(domain) => domains.push(domain.to_string()),
+                        _ => return Err(format!("unsupported domain `{}`", domain)),
+                    }
+                }
+            }
+            name if IGNORED_OPTIONS.contains(&name) => {}
+            name => return Err(format!("unsupported option `{}`", name)),
+        }
+    }
+
+    let removal = removal.expect("One of the options is removeparam");
+
+    match pattern {
+        "" | "*" => {}
+        pattern => {
+            let domain = pattern
+                .strip_prefix("||")
+                .map(|rest| rest.trim_end_matches(['^', '/']))
+                .filter(|domain| is_domain(domain))
+                .ok_or_else(|| format!("unsupported URL pattern `{}`", pattern))?;
+            domains.push(domain.to_string());
+        }
+    }
+
+    if exception {
+        if !matches!(removal, Removal::All) {
+            return Err(
+                "exceptions for single parameters can't be expressed, only for whole sites"
+                    .to_string(),
+            );
+        }
+        if domains.is_empty() || !excluded.is_empty() {
+            return Err("exceptions need to name the domains they apply to".to_string());
+        }
+    } else if matches!(removal, Removal::All) && domains.is_empty() {
+        return Err("removing every parameter from every site".to_string());
+    }
+
+    Ok(Filter {
+        exception,
+        removal,
+        domains,
+        excluded,
+    })
+}
+
+fn parse_removal(value: Option<&str>) -> Result<Removal, String> {
+    let Some(value) = value.filter(|value| !value.is_empty()) else {
+        return Ok(Removal::All);
+    };
+
+    if value.starts_with('~') {
+        return Err("keeping only some parameters (`~`)".to_string());
+    }
+
+    let Some(body) = value.strip_prefix('/') else {
+        // AdGuard escapes commas in parameter names
+        return Ok(Removal::Name(value.replace("\\,", ",")));
+    };
+
+    let (regex, flags) = body
+        .rsplit_once('/')
+        .ok_or_else(|| format!("unterminated regex `{}`", value))?;
+    if flags.chars().any(|flag| flag != 'i') {
+        return Err(format!("unsupported regex flags `{}`", flags));
+    }
+    let regex = regex.replace("\\,", ",");
+
+    // The regex runs over `name=value`, which a parameter name rule can only follow as long as it
+    // stays clear of the value
+    let unescaped_end = regex.ends_with('$') && !regex.ends_with("\\$");
+    if regex.contains('=') || unescaped_end {
+        return Err("regex matches parameter values".to_string());
+    }
+
+    // Parameter rules match the whole name, this one may match anywhere in it
+    let pattern = match regex.strip_prefix('^') {
+        Some(anchored) => format!("(?:{}).*", anchored),
+        None => format!(".*(?:{}).*", regex),
+    };
+    Ok(Removal::Pattern(pattern))
+}
+
+/// Split filter options on commas, other than the ones escaped or inside a regex
+fn split_options(options: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_regex = false;
+    let mut escaped = false;
+
+    for (i, c) in options.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '/' if options[start..i].ends_with('=') => in_regex = true,
+            '/' if in_regex => in_regex = false,
+            ',' if !in_regex => {
+                parts.push(&options[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    parts.push(&options[start..]);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CleaningOptions, RuleSet, RuleSource, UrlCleaner};
+
+    #[test]
+    fn test_import_removeparam_filters() {
+        let list = r#"
+! Title: test list
+[Adblock Plus 2.0]
+$removeparam=mc_eid
+$removeparam=/^ref_/
+||shop.test^$removeparam=tag
+$removeparam=src,domain=news.test|~live.news.test
+@@||bank.test^$removeparam
+||shop.test^$removeparam=/utm=x/
+example.com##.ad
+$removeparam=~keep
+"#;
+
+        let import = import(list);
+        let reasons: Vec<&str> = import
+            .unsupported
+            .iter()
+            .map(|u| u.reason.as_str())
+            .collect();
+        assert_eq!(
+            reasons,
+            vec![
+                "regex matches parameter values",
+                "cosmetic filter",
+                "keeping only some parameters (`~`)"
+            ]
+        );
+        assert_eq!(import.unsupported[0].line, 9);
+
+        let rules = RuleSet::from_file(&import.file, RuleSource::User("list.txt".into())).unwrap();
+        let cleaner = UrlCleaner::with_rules(CleaningOptions::default(), &rules).unwrap();
+        let clean = |url: &str| cleaner.clean_url(url).unwrap().url;
+
+        assert_eq!(
+            clean("https://a.test/?id=1&mc_eid=2&ref_src=3&tag=4"),
+            "https://a.test/?id=1&tag=4"
+        );
+        assert_eq!(
+            clean("https://www.shop.test/?tag=4&id=1"),
+            "https://www.shop.test/?id=1"
+        );
+        assert_eq!(clean("https://news.test/?src=rss"), "https://news.test/");
+        assert_eq!(
+            clean("https://live.news.test/?src=rss"),
+            "https://live.news.test/?src=rss"
+        );
+        assert_eq!(
+            clean("https://bank.test/?mc_eid=1"),
+            "https://bank.test/?mc_eid=1"
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Brave's query filter list (`brave/query-filter`), a JSON array of entries like
+//! `{"include": ["*://*/*"], "exclude": ["*://*.example.com/*"], "params": ["fbclid"]}`.
+//!
+//! `include` and `exclude` are WebExtension match patterns, which become the provider's urlPattern and
+//! exceptions.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::{Import, Providers, Unsupported};
+use crate::ProviderSpec;
+
+/// Matches every URL
+const ALL_URLS: &str = "*://*/*";
+
+#[derive(Debug, Deserialize)]
+struct Entry {
+    #[serde(default)]
+    include: Vec<String>,
+    #[serde(default)]
+    exclude: Vec<String>,
+    #[serde(default)]
+    params: Vec<String>,
+}
+
+pub fn import(contents: &str) -> Result<Import> {
+    let entries: Vec<Entry> =
+        serde_json::from_str(contents).context("Failed to parse the query filter list")?;
+
+    let mut providers = Providers::new("brave");
+    let mut unsupported = Vec::new();
+
+    for (i, entry) in entries.iter().enumerate() {
+        let mut report = |text: &str, reason: &str| {
+            unsupported.push(Unsupported {
+                line: 0,
+                text: text.to_string(),
+                reason: format!("{} in entry {}", reason, i + 1),
+            })
+        };
+
+        let include: Vec<&str> = entry.include.iter().map(String::as_str).collect();
+        let url_pattern = if include.is_empty() || include.contains(&ALL_URLS) {
+            None
+        } else {
+            let patterns: Vec<String> = include
+                .iter()
+                .filter_map(|pattern| {
+                    let regex = match_pattern(pattern);
+                    if regex.is_none() {
+                        report(pattern, "not a match pattern");
+                    }
+                    regex
+                })
+                .collect();
+
+            if patterns.is_empty() {
+                continue;
+            }
+            Some(format!("(?:{})", patterns.join("|")))
+        };
+
+        let exceptions: Vec<String> = entry
+            .exclude
+            .iter()
+            .filter_map(|pattern| {
+                let regex = match_pattern(pattern);
+                if regex.is_none() {
+                    report(pattern, "not a match pattern");
+                }
+                regex
+            })
+            .collect();
+
+        let provider = match url_pattern {
+            None if exceptions.is_empty() => providers.global(),
+            url_pattern => {
+                // Name the provider after the hosts it's for, keeping entries with the same hosts apart
+                // when their exceptions differ
+                let hosts: Vec<&str> = include.iter().filter_map(|p| host(p)).collect();
+                let base = if hosts.is_empty() {
+                    "brave".to_string()
+                } else {
+                    format!("brave:{}", hosts.join("|"))
+                };
+
+                let spec = ProviderSpec {
+                    url_pattern: Some(url_pattern.unwrap_or_else(|| ".*".to_string())),
+                    exceptions,
+                    ..Default::default()
+                };
+                let name = match providers.providers.get(&base) {
+                    Some(existing)
+                        if existing.url_pattern != spec.url_pattern
+                            || existing.exceptions != spec.exceptions =>
+                    {
+                        format!("{}#{}", base, i + 1)
+                    }
+                    _ => base,
+                };
+                providers.providers.entry(name).or_insert(spec)
+            }
+        };
+
+        for param in &entry.params {
+            if !provider.params.contains(param) {
+                provider.params.push(param.clone());
+            }
+        }
+    }
+
+    Ok(Import {
+        file: providers.into_file(),
+        unsupported,
+    })
+}
+
+/// The host part of a match pattern
+fn host(pattern: &str) -> Option<&str> {
+    let (_, rest) = pattern.split_once("://")?;
+    rest.split('/').next()
+}
+
+/// Translate a WebExtension match pattern (`<scheme>://<host>/<path>`) into a URL regex
+fn match_pattern(pattern: &str) -> Option<String> {
+    if pattern == "<all_urls>" {
+        return Some(".*".to_string());
+    }
+
+    let (scheme, rest) = pattern.split_once("://")?;
+    let (host, path) = match rest.find('/') {
+        Some(slash) => rest.split_at(slash),
+        None => return None,
+    };
+
+    let scheme = match scheme {
+        "*" => "https?",
+        "http" | "https" => scheme,
+        _ => return None,
+    };
+
+    let host = match host {
+        "*" => "[^/]+".to_string(),
+        host if host.starts_with("*.") => {
+            format!(r"(?:[^/]+\.)?{}", regex::escape(&host[2..]))
+        }
+        host if !host.contains('*') && !host.is_empty() => regex::escape(host),
+        _ => return None,
+    };
+
+    let path = path
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(".*");
+
+    Some(format!(r"^{}://{}(?::\d+)?{}", scheme, host, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{CleaningOptions, RuleSet, RuleSource, UrlCleaner};
+
+    #[test]
+    fn test_import_brave_query_filter() {
+        let list = r#"[
+            {"include": ["*://*/*"], "exclude": [], "params": ["fbclid", "mc_eid"]},
+            {"include": ["*://*/*"], "exclude": ["*://*.bank.test/*"], "params": ["_hsenc"]},
+            {"include": ["*://*.instagram.test/*"], "exclude": [], "params": ["igshid"]},
+            {"include": ["ftp://files.test/*"], "exclude": [], "params": ["x"]}
+        ]"#;
+
+        let brave = import(list).unwrap();
+        assert_eq!(brave.unsupported.len(), 1);
+        assert_eq!(brave.unsupported[0].line, 0);
+        assert_eq!(
+            brave.unsupported[0].reason,
+            "not a match pattern in entry 4"
+        );
+
+        let rules = RuleSet::from_file(&brave.file, RuleSource::User("brave.json".into())).unwrap();
+        let cleaner = UrlCleaner::with_rules(CleaningOptions::default(), &rules).unwrap();
+        let clean = |url: &str| cleaner.clean_url(url).unwrap().url;
+
+        assert_eq!(
+            clean("https://a.test/?fbclid=1&_hsenc=2&igshid=3"),
+            "https://a.test/?igshid=3"
+        );
+        assert_eq!(
+            clean("https://www.bank.test/?_hsenc=2"),
+            "https://www.bank.test/?_hsenc=2"
+        );
+        assert_eq!(
+            clean("https://www.instagram.test/p/1?igshid=3"),
+            "https://www.instagram.test/p/1"
+        );
+    }
+}
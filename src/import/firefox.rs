@@ -0,0 +1,173 @@
+//! Firefox query stripping lists.
+//!
+//! Accepts the remote-settings `query-stripping` collection (a `{"data": [...]}` response, an array of
+//! records or a single record with `stripList` and `allowList`), a `prefs.js`/`user.js` setting
+//! `privacy.query_stripping.strip_list` and `allow_list`, or a bare whitespace separated list of names.
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use super::{Import, Providers, Unsupported, domain_pattern, is_domain};
+
+const STRIP_LIST_PREF: &str = "privacy.query_stripping.strip_list";
+const ALLOW_LIST_PREF: &str = "privacy.query_stripping.allow_list";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct Record {
+    strip_list: Vec<String>,
+    allow_list: Vec<String>,
+    /// The line of each `allow_list` entry, for lists that have lines
+    #[serde(skip)]
+    allow_lines: Vec<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum Collection {
+    Response { data: Vec<Record> },
+    Records(Vec<Record>),
+    Record(Record),
+}
+
+pub fn import(contents: &str) -> Result<Import> {
+    let trimmed = contents.trim_start();
+
+    let records = if trimmed.starts_with('{') || trimmed.starts_with('[') {
+        let collection: Collection = serde_json::from_str(contents)
+            .context("Failed to parse the query-stripping records")?;
+        match collection {
+            Collection::Response { data } => data,
+            Collection::Records(records) => records,
+            Collection::Record(record) => vec![record],
+        }
+    } else if contents.contains(STRIP_LIST_PREF) || contents.contains(ALLOW_LIST_PREF) {
+        let (allow_lines, allow_list) = pref(contents, ALLOW_LIST_PREF, ',').into_iter().unzip();
+        vec![Record {
+            strip_list: pref(contents, STRIP_LIST_PREF, ' ')
+                .into_iter()
+                .map(|(_, param)| param)
+                .collect(),
+            allow_list,
+            allow_lines,
+        }]
+    } else {
+        vec![Record {
+            strip_list: contents.split_whitespace().map(str::to_string).collect(),
+            ..Record::default()
+        }]
+    };
+
+    let mut providers = Providers::new("firefox");
+    let mut unsupported = Vec::new();
+    let mut allowed = Vec::new();
+
+    // Firefox strips the union of every record, and exempts the union of their allow lists
+    for (i, record) in records.iter().enumerate() {
+        for param in &record.strip_list {
+            let param = param.trim();
+            if param.is_empty() {
+                continue;
+            }
+
+            let global = providers.global();
+            if !global.params.iter().any(|p| p.eq_ignore_ascii_case(param)) {
+                global.params.push(param.to_string());
+            }
+        }
+
+        for (j, site) in record.allow_list.iter().enumerate() {
+            let site = site.trim();
+            if site.is_empty() {
+                continue;
+            }
+
+            if is_domain(site) {
+                allowed.push(site.to_string());
+            } else {
+                // JSON records have no lines to point at, so they're named in the reason instead
+                let (line, reason) = match record.allow_lines.get(j) {
+                    Some(line) => (*line, "allow list entry is not a site".to_string()),
+                    None => (
+                        0,
+                        format!("allow list entry of record {} is not a site", i + 1),
+                    ),
+                };
+                unsupported.push(Unsupported {
+                    line,
+                    text: site.to_string(),
+                    reason,
+                });
+            }
+        }
+    }
+
+    let global = providers.global();
+    for site in allowed {
+        let pattern = domain_pattern(&site);
+        if !global.exceptions.contains(&pattern) {
+            global.exceptions.push(pattern);
+        }
+    }
+
+    Ok(Import {
+        file: providers.into_file(),
+        unsupported,
+    })
+}
+
+/// The value of a string pref set with `user_pref("name", "value");` or `pref(...)`, split on `separator`,
+/// along with the line of each item
+fn pref(contents: &str, name: &str, separator: char) -> Vec<(usize, String)> {
+    let quoted = format!("\"{}\"", name);
+
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let rest = line.split_once(quoted.as_str())?.1;
+            let value = rest
+                .trim_start()
+                .strip_prefix(',')?
+                .trim_start()
+                .strip_prefix('"')?;
+            value.split_once('"').map(|(value, _)| (i + 1, value))
+        })
+        .flat_map(|(line, value)| value.split(separator).map(move |item| (line, item.trim())))
+        .filter(|(_, item)| !item.is_empty())
+        .map(|(line, item)| (line, item.to_string()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_firefox_lists() {
+        let prefs = r#"
+user_pref("privacy.query_stripping.strip_list", "mc_eid oly_anon_id __s");
+user_pref("privacy.query_stripping.allow_list", "example.org, https://bad/");
+"#;
+        let from_prefs = import(prefs).unwrap();
+        let firefox = &from_prefs.file.providers["firefox"];
+        assert_eq!(firefox.params, vec!["mc_eid", "oly_anon_id", "__s"]);
+        assert_eq!(firefox.exceptions.len(), 1);
+        assert_eq!(from_prefs.unsupported[0].text, "https://bad/");
+        assert_eq!(from_prefs.unsupported[0].line, 3);
+
+        let records = r#"{"data": [
+            {"id": "a", "stripList": ["mc_eid", "vero_id"], "allowList": []},
+            {"id": "b", "stripList": ["MC_EID", "_hsenc"], "allowList": ["www.example.com", "*"]}
+        ]}"#;
+        let from_records = import(records).unwrap();
+        let firefox = &from_records.file.providers["firefox"];
+        assert_eq!(firefox.params, vec!["mc_eid", "vero_id", "_hsenc"]);
+        assert_eq!(firefox.exceptions.len(), 1);
+        assert_eq!(from_records.unsupported[0].line, 0);
+        assert_eq!(
+            from_records.unsupported[0].reason,
+            "allow list entry of record 2 is not a site"
+        );
+    }
+}
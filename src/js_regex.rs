@@ -0,0 +1,291 @@
+//! Translation of JavaScript regex syntax (which the ClearURLs rules are written in) to Rust's `regex`.
+//!
+//! This only depends on `std`, so `build.rs` can pull it in to check the embedded rules at build time.
+
+/// How a pattern is going to be used, which decides what can safely be rewritten
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Usage {
+    /// Tested against whole URLs (urlPattern, exceptions, redirections)
+    Url,
+    /// Used to rewrite URLs (raw rules), so the exact match span matters
+    Replace,
+    /// Tested against a whole query parameter name, case insensitively
+    Key,
+    /// Tested against a whole (decoded) query parameter value, case sensitively
+    Value,
+}
+
+/// A pattern rewritten for Rust's regex syntax
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Translated {
+    /// The rewritten pattern
+    pub pattern: String,
+    /// JavaScript-only constructs that were rewritten
+    pub rewrites: Vec<&'static str>,
+    /// Why the pattern still needs a backtracking engine, if it does
+    pub unsupported: Option<&'static str>,
+}
+
+/// Rewrite the JavaScript-only constructs of a pattern into their Rust equivalents
+pub fn translate(pattern: &str, usage: Usage) -> Translated {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut out = String::with_capacity(pattern.len());
+    let mut rewrites = Vec::new();
+    let mut unsupported = None;
+    let mut in_class = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+
+        match c {
+            '\\' => {
+                let Some(escaped) = next else {
+                    // A trailing backslash is invalid in both flavours, leave it for the compiler to report
+                    out.push('\\');
+                    i += 1;
+                    continue;
+                };
+                i += 2;
+
+                match escaped {
+                    // Slashes only need escaping inside JS regex literals
+                    '/' => {
+                        out.push('/');
+                        rewrite(&mut rewrites, "escaped slash");
+                    }
+                    '0' if !chars.get(i).is_some_and(char::is_ascii_digit) => {
+                        out.push_str(r"\x00");
+                        rewrite(&mut rewrites, "null escape");
+                    }
+                    '1'..='9' if !in_class => {
+                        out.push('\\');
+                        out.push(escaped);
+                        unsupported = Some("backreference");
+                    }
+                    'k' if chars.get(i) == Some(&'<') => {
+                        out.push_str(r"\k");
+                        unsupported = Some("backreference");
+                    }
+                    'c' if chars.get(i).is_some_and(char::is_ascii_alphabetic) => {
+                        let control = chars[i] as u8 % 32;
+                        out.push_str(&format!(r"\x{:02X}", control));
+                        rewrite(&mut rewrites, "control escape");
+                        i += 1;
+                    }
+                    // Backspace inside a class
+                    'b' if in_class => {
+                        out.push_str(r"\x08");
+                        rewrite(&mut rewrites, "backspace escape");
+                    }
+                    'd' | 'D' | 'w' | 'W' | 's' | 'S' | 'b' | 'B' | 't' | 'n' | 'v' | 'f' | 'r'
+                    | 'x' | 'u' => {
+                        out.push('\\');
+                        out.push(escaped);
+                    }
+                    // JS treats any other escaped letter as the letter itself
+                    escaped if escaped.is_alphanumeric() => {
+                        out.push(escaped);
+                        rewrite(&mut rewrites, "identity escape");
+                    }
+                    // `\<` and `\>` are word boundaries in Rust
+                    '<' | '>' => {
+                        out.push(escaped);
+                        rewrite(&mut rewrites, "identity escape");
+                    }
+                    escaped if escaped.is_ascii() => {
+                        out.push('\\');
+                        out.push(escaped);
+                    }
+                    escaped => {
+                        out.push(escaped);
+                        rewrite(&mut rewrites, "identity escape");
+                    }
+                }
+            }
+            '[' if !in_class => {
+                let negated = next == Some('^');
+                let body = if negated { i + 2 } else { i + 1 };
+
+                // `[]` never matches and `[^]` matches anything, while Rust reads `]` as the first class member
+                if chars.get(body) == Some(&']') {
+                    out.push_str(if negated { r"[\s\S]" } else { r"[^\s\S]" });
+                    rewrite(&mut rewrites, "empty class");
+                    i = body + 1;
+                    continue;
+                }
+
+                out.push('[');
+                if negated {
+                    out.push('^');
+                }
+                in_class = true;
+                i = body;
+            }
+            // Rust supports nested classes and set operations, JS reads these literally
+            '[' | '&' | '~' if in_class => {
+                out.push('\\');
+                out.push(c);
+                i += 1;
+            }
+            '-' if in_class && next == Some('-') => {
+                out.push_str(r"\-");
+                i += 1;
+            }
+            ']' if in_class => {
+                out.push(']');
+                in_class = false;
+                i += 1;
+            }
+            '(' if !in_class && next == Some('?') => {
+                let rest: String = chars[i..chars.len().min(i + 4)].iter().collect();
+                let lookahead = rest.starts_with("(?=") || rest.starts_with("(?!");
+                let lookbehind = rest.starts_with("(?<=") || rest.starts_with("(?<!");
+
+                if !lookahead && !lookbehind {
+                    out.push('(');
+                    i += 1;
+                    continue;
+                }
+
+                let prefix = if lookahead { 3 } else { 4 };
+                let negative = rest.as_bytes()[prefix - 1] == b'!';
+                let end = group_end(&chars, i);
+
+                // A positive lookaround on the edge of a pattern that's only ever tested for a match
+                // can just consume what it looks at. After a `^` a lookbehind can never match, so
+                // consuming would change what the pattern matches.
+                let at_edge = if lookahead {
+                    end == Some(chars.len() - 1)
+                } else {
+                    out.is_empty()
+                };
+
+                if usage == Usage::Url && !negative && at_edge {
+                    out.push_str("(?:");
+                    rewrite(&mut rewrites, "lookaround");
+                } else {
+                    out.extend(&chars[i..i + prefix]);
+                    unsupported = Some("lookaround");
+                }
+                i += prefix;
+            }
+            // JS reads a brace literally unless it forms a valid quantifier
+            '{' if !in_class => match quantifier_end(&chars, i) {
+                Some(end) => {
+                    out.extend(&chars[i..=end]);
+                    i = end + 1;
+                }
+                None => {
+                    out.push_str(r"\{");
+                    i += 1;
+                }
+            },
+            '}' if !in_class => {
+                out.push_str(r"\}");
+                i += 1;
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+
+    match usage {
+        Usage::Key => out = format!("(?i)^(?:{})$", out),
+        Usage::Value => out = format!("^(?:{})$", out),
+        Usage::Url | Usage::Replace => {}
+    }
+
+    Translated {
+        pattern: out,
+        rewrites,
+        unsupported,
+    }
+}
+
+fn rewrite(rewrites: &mut Vec<&'static str>, name: &'static str) {
+    if !rewrites.contains(&name) {
+        rewrites.push(name);
+    }
+}
+
+/// Index of the `)` closing the group opened at `start`
+fn group_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_class = false;
+    let mut i = start;
+
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '[' if !in_class => in_class = true,
+            ']' if in_class => in_class = false,
+            '(' if !in_class => depth += 1,
+            ')' if !in_class => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    None
+}
+
+/// Index of the `}` ending a `{n}`, `{n,}` or `{n,m}` quantifier starting at `start`
+fn quantifier_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut i = start + 1;
+    let mut digits = 0;
+
+    while chars.get(i).is_some_and(char::is_ascii_digit) {
+        i += 1;
+        digits += 1;
+    }
+    if digits == 0 {
+        return None;
+    }
+
+    if chars.get(i) == Some(&',') {
+        i += 1;
+        while chars.get(i).is_some_and(char::is_ascii_digit) {
+            i += 1;
+        }
+    }
+
+    (chars.get(i) == Some(&'}')).then_some(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_translate_js_syntax() {
+        let translated = translate(r"^https?:\/\/example\.com\/[^]{2}x{,3}", Usage::Url);
+        assert_eq!(
+            translated.pattern,
+            r"^https?://example\.com/[\s\S]{2}x\{,3\}"
+        );
+        assert_eq!(translated.unsupported, None);
+
+        let translated = translate(r"^https?:\/\/a\.com\/(?=track)", Usage::Url);
+        assert_eq!(translated.pattern, r"^https?://a\.com/(?:track)");
+
+        let translated = translate(r"\/ref=(?=track)", Usage::Replace);
+        assert_eq!(translated.unsupported, Some("lookaround"));
+
+        let translated = translate(r"(?<=\/)ref=", Usage::Url);
+        assert_eq!(translated.pattern, r"(?:/)ref=");
+        let translated = translate(r"^(?<=a)b", Usage::Url);
+        assert_eq!(translated.unsupported, Some("lookaround"));
+
+        let translated = translate("utm_[a-z]+", Usage::Key);
+        assert_eq!(translated.pattern, "(?i)^(?:utm_[a-z]+)$");
+    }
+}
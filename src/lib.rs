@@ -1,21 +1,129 @@
-use std::collections::HashSet;
-
 use anyhow::{Context, Result};
 use log::{debug, info, warn};
-use regex::{Regex, RegexBuilder};
 use serde::{Deserialize, Serialize};
 use tracing::instrument;
 use tsify::Tsify;
 use url::Url;
 
-use crate::rules::{PROVIDERS, Provider};
+use crate::allowlist::Allowlist;
+use crate::heuristics::SUSPICIOUS;
+pub use crate::js_regex::Usage;
+use crate::neat_url::NeatRule;
+pub use crate::pattern::Pattern;
+pub use crate::ruleset::{
+    MergeMode, Provider, ProviderSpec, Rule, RuleFile, RuleSet, RuleSource, RulesetInfo,
+    SkippedRule, ValueRule, ValueRuleSpec,
+};
 
+mod allowlist;
+pub mod author;
+mod canonical;
+pub mod cases;
+mod decode;
+pub mod diff;
+pub mod export;
+mod fragment;
+mod heuristics;
+pub mod import;
+mod js_regex;
+pub mod lint;
+pub mod mine;
+mod minimal;
+mod neat_url;
+mod nested;
+mod pattern;
+mod proxy;
 mod rules;
+mod ruleset;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod update;
 mod wasm;
 
+/// Which rules `UrlCleaner::with_cached_rules` and the CLI run: the cached ruleset from `plink rules update`, or the embedded one
+pub fn ruleset_info() -> RulesetInfo {
+    RuleSet::active().info().clone()
+}
+
+/// The kind of rule that fired
+#[derive(Debug, Tsify, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleKind {
+    /// A query parameter was removed
+    Param,
+    /// A raw rule rewrote the whole URL
+    Raw,
+    /// The URL was unwrapped from a redirector
+    Redirect,
+    /// The request was blocked by a complete provider
+    Cancel,
+}
+
+impl std::fmt::Display for RuleKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            RuleKind::Param => "param",
+            RuleKind::Raw => "raw",
+            RuleKind::Redirect => "redirect",
+            RuleKind::Cancel => "cancel",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A rule that fired while cleaning, along with where it was defined
+#[derive(Debug, Tsify, Serialize, Deserialize, Clone, PartialEq)]
+pub struct RuleMatch {
+    /// Name of the provider the rule belongs to
+    pub provider: String,
+    /// What the rule did
+    pub kind: RuleKind,
+    /// The pattern (or parameter name) that matched
+    pub pattern: String,
+    /// `built-in` for the embedded rules, otherwise the user rule file it came from
+    pub source: String,
+    /// How a redirect target was decoded, like `percent` then `base64`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub decoding: Vec<String>,
+}
+
+impl RuleMatch {
+    fn new(provider: &Provider, kind: RuleKind, rule: &Rule) -> Self {
+        Self {
+            provider: provider.name.clone(),
+            kind,
+            pattern: rule.as_str().to_string(),
+            source: rule.source.to_string(),
+            decoding: Vec::new(),
+        }
+    }
+}
+
+/// A parameter strict mode kept, and the entry that kept it
+#[derive(Debug, Tsify, Serialize, Deserialize, Clone, PartialEq)]
+pub struct KeptParam {
+    /// The parameter name
+    pub name: String,
+    /// The allowlist entry (or `!` exclusion), in Neat URL syntax
+    pub rule: String,
+    /// `built-in` for the built-in allowlists, otherwise `options`
+    pub source: String,
+}
+
+/// A parameter the heuristics flagged as a likely tracker
+#[derive(Debug, Tsify, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SuspiciousParam {
+    /// The parameter name
+    pub name: String,
+    /// From 0 to 1, higher is more likely a tracker
+    pub score: f64,
+    /// The signals that added up to the score, like `tracker-like name` or `UUID value`
+    pub reasons: Vec<String>,
+    /// Whether the score reached `heuristic_threshold`, so the parameter was removed
+    pub removed: bool,
+}
+
 /// Result of URL cleaning operation
 #[derive(Debug, Tsify, Serialize, Deserialize, Clone)]
-#[tsify(into_wasm_abi, from_wasm_abi)]
 pub struct CleaningResult {
     /// The cleaned URL string
     pub url: String,
@@ -27,11 +135,93 @@ pub struct CleaningResult {
     pub cancel: bool,
     /// Which rules were applied
     pub applied_rules: Vec<String>,
+    /// Every individual rule that fired, with its source
+    #[serde(default)]
+    pub rule_matches: Vec<RuleMatch>,
+    /// The parameters strict mode kept, and why
+    #[serde(default)]
+    pub kept_params: Vec<KeptParam>,
+    /// Parameters the heuristics flagged, when they're on
+    #[serde(default)]
+    pub suspicious_params: Vec<SuspiciousParam>,
+    /// The site whose minimal form the URL was reduced to, like `amazon`, when `minimal_form` is on
+    #[serde(default)]
+    pub minimal_form: Option<String>,
+}
+
+/// Which query parameters survive cleaning
+#[derive(Debug, Tsify, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CleaningMode {
+    /// Everything the rules don't remove
+    #[default]
+    Rules,
+    /// Only allowlisted parameters, everything else is removed
+    Strict,
+}
+
+/// What to do with the slash at the end of the path (the root path always keeps it)
+#[derive(Debug, Tsify, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrailingSlash {
+    /// Leave it as it is
+    #[default]
+    Keep,
+    /// Add one, unless the last segment looks like a file name
+    Add,
+    /// Remove it
+    Remove,
+}
+
+/// Normalizations run after cleaning, all off by default. Each one that changes a URL is added to
+/// `applied_rules` under its field name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Tsify, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Canonicalization {
+    /// `Example.COM` to `example.com`
+    pub lowercase_host: bool,
+    /// `:443` on https and `:80` on http
+    pub remove_default_port: bool,
+    /// `example.com.` to `example.com`
+    pub strip_trailing_dot: bool,
+    /// Decode escaped unreserved characters (`%7E` to `~`) and uppercase the hex of the rest (`%2f` to `%2F`)
+    pub normalize_percent_encoding: bool,
+    /// Sort query parameters by name, keeping the order of repeated ones
+    pub sort_params: bool,
+    /// Remove parameters without a value (`a=`) and stray `&`s
+    pub remove_empty_params: bool,
+    /// `/a//b` to `/a/b`
+    pub collapse_slashes: bool,
+    pub trailing_slash: TrailingSlash,
+    /// `www.example.com` to `example.com`, which isn't always the same site
+    pub strip_www: bool,
+}
+
+impl Canonicalization {
+    /// Every normalization that keeps the URL pointing at the same resource, leaving the trailing slash and
+    /// `www.` alone
+    pub fn standard() -> Self {
+        Self {
+            lowercase_host: true,
+            remove_default_port: true,
+            strip_trailing_dot: true,
+            normalize_percent_encoding: true,
+            sort_params: true,
+            remove_empty_params: true,
+            collapse_slashes: true,
+            ..Default::default()
+        }
+    }
+
+    /// Whether any normalization is on
+    pub fn is_enabled(&self) -> bool {
+        *self != Self::default()
+    }
 }
 
 /// Configuration options for URL cleaning
 #[derive(Debug, Clone, Tsify, Serialize, Deserialize)]
-#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(default)]
 pub struct CleaningOptions {
     /// Whether to skip localhost URLs
     pub skip_localhost: bool,
@@ -39,49 +229,120 @@ pub struct CleaningOptions {
     pub apply_referral_marketing: bool,
     /// Whether to enable domain blocking
     pub domain_blocking: bool,
-    /// Additional blocked parameters (like the neat_url approach)
+    /// Additional rules in Neat URL syntax: `param`, `utm_*`, `param@*.example.com`, `$/ref@amazon.*`, `$$`
+    /// and `!param@example.com` exclusions
     pub additional_blocked_params: Vec<String>,
     /// Domains to exclude from cleaning
     pub blacklisted_domains: Vec<String>,
+    /// Whether to apply the parameter rules to hash-routed query strings and `key=value` fragments
+    pub clean_fragments: bool,
+    /// Whether to strip text directives (`#:~:text=`) from fragments
+    pub strip_text_fragments: bool,
+    /// Whether to unwrap AMP caches and proxies (`google.com/amp/s/...`, `*.translate.goog`) before cleaning
+    pub unwrap_proxies: bool,
+    /// Whether to clean the URLs inside archive and share links (`web.archive.org/web/*/https://...`,
+    /// `facebook.com/sharer/sharer.php?u=...`), keeping the wrapper
+    pub clean_nested: bool,
+    /// Whether to keep only allowlisted query parameters
+    pub mode: CleaningMode,
+    /// Parameters to keep in strict mode on top of the built-in allowlists, in Neat URL syntax: `v@*.youtube.com`,
+    /// `page`, `session*@app.example.com`
+    pub allowed_params: Vec<String>,
+    /// Whether to score the parameters the rules left for how much they look like trackers
+    pub heuristics: bool,
+    /// Remove parameters scoring at least this much (from 0 to 1), with `heuristics` on
+    pub heuristic_threshold: Option<f64>,
+    /// Whether to reduce URLs of known sites to their minimal form, like `/dp/<ASIN>` on Amazon
+    pub minimal_form: bool,
+    /// Normalizations to run once the URL is clean
+    pub canonicalize: Canonicalization,
 }
 
 impl Default for CleaningOptions {
     fn default() -> Self {
         Self {
-            skip_localhost: true,                  // Ignore the local domains
-            apply_referral_marketing: true,        // Strip referral marketing
-            domain_blocking: true,                 // Block certain domains
-            additional_blocked_params: Vec::new(), // Empty extra params
-            blacklisted_domains: Vec::new(),       // Empty blacklist
+            skip_localhost: true,                      // Ignore the local domains
+            apply_referral_marketing: true,            // Strip referral marketing
+            domain_blocking: true,                     // Block certain domains
+            additional_blocked_params: Vec::new(),     // Empty extra params
+            blacklisted_domains: Vec::new(),           // Empty blacklist
+            clean_fragments: true,                     // Clean `#/page?utm_source=x`
+            strip_text_fragments: false,               // Keep `#:~:text=` highlights
+            unwrap_proxies: true,                      // Clean the page behind AMP links
+            clean_nested: true,                        // Clean archived and shared URLs
+            mode: CleaningMode::Rules,                 // Only remove what the rules match
+            allowed_params: Vec::new(),                // Just the built-in allowlists
+            heuristics: false,                         // Only the rules decide
+            heuristic_threshold: None,                 // Report suspicious params, keep them
+            minimal_form: false,                       // Keep what the rules left
+            canonicalize: Canonicalization::default(), // Keep the URL as written
         }
     }
 }
 
+/// How many redirects `UrlCleaner::dedup_key` follows
+const MAX_REDIRECTS: usize = 5;
+
+/// How deep URLs inside archive and share links are cleaned, like a shared archive link
+const MAX_NESTING: usize = 3;
+
 /// Main URL cleaner that applies rules to sanitize URLs
 #[derive(Debug)]
 pub struct UrlCleaner<'a> {
-    providers: &'a [Provider],
+    rules: &'a RuleSet,
     options: CleaningOptions,
+    /// `additional_blocked_params`, parsed
+    neat_rules: Vec<NeatRule>,
+    /// The built-in allowlists and `allowed_params`, for strict mode
+    allowlist: Allowlist,
 }
 
-impl<'a> UrlCleaner<'a> {
-    /// Create a new URL cleaner from configuration
+impl UrlCleaner<'static> {
+    /// Create a new URL cleaner from configuration, using the embedded rules
     pub fn new(options: CleaningOptions) -> Result<Self> {
+        Self::with_rules(options, RuleSet::embedded())
+    }
+
+    /// Create a new URL cleaner from configuration, using the cached rules from `plink rules update` if there are
+    /// any and the embedded rules otherwise
+    pub fn with_cached_rules(options: CleaningOptions) -> Result<Self> {
+        Self::with_rules(options, RuleSet::active())
+    }
+}
+
+impl<'a> UrlCleaner<'a> {
+    /// Create a new URL cleaner that runs over a custom ruleset (e.g. the embedded rules plus user overlays)
+    pub fn with_rules(options: CleaningOptions, rules: &'a RuleSet) -> Result<Self> {
         info!(
             "Initializing URL cleaner with {} providers",
-            PROVIDERS.len()
+            rules.providers().len()
         );
 
+        let neat_rules = options
+            .additional_blocked_params
+            .iter()
+            .map(|rule| NeatRule::parse(rule))
+            .collect::<Result<_>>()?;
+        let allowlist = Allowlist::new(&options.allowed_params)?;
+
         Ok(Self {
-            providers: PROVIDERS,
+            rules,
             options,
+            neat_rules,
+            allowlist,
         })
     }
 
     /// Clean a URL by removing tracking parameters
     #[instrument]
     pub fn clean_url(&self, url: &str) -> Result<CleaningResult> {
+        self.clean_url_to_depth(url, MAX_NESTING)
+    }
+
+    /// Clean a URL, and the URLs embedded in it down to `depth` levels
+    fn clean_url_to_depth(&self, url: &str, depth: usize) -> Result<CleaningResult> {
         // We need to make this owned for the base manipulation
+        let input = url;
         let mut url = url.to_string();
 
         // Add the boilerplate if it's not present
@@ -93,8 +354,19 @@ impl<'a> UrlCleaner<'a> {
 
         debug!("Cleaning URL: {}", url);
 
-        // Check if we should skip this URL
-        if self.should_skip_url(&url) {
+        // Wrappers spread the origin over their own URL, which no provider redirection can capture
+        let unwrapped = if self.options.unwrap_proxies {
+            proxy::unwrap(&url)
+        } else {
+            None
+        };
+
+        // Check if we should skip this URL, or the origin behind it
+        if self.should_skip_url(&url)
+            || unwrapped
+                .as_ref()
+                .is_some_and(|(origin, _)| self.should_skip_url(origin))
+        {
             debug!("Skipping URL due to configuration: {}", url);
             return Ok(CleaningResult {
                 url: url.to_string(),
@@ -102,20 +374,42 @@ impl<'a> UrlCleaner<'a> {
                 redirect: false,
                 cancel: false,
                 applied_rules: Vec::new(),
+                rule_matches: Vec::new(),
+                kept_params: Vec::new(),
+                suspicious_params: Vec::new(),
+                minimal_form: None,
             });
         }
 
         let original_url = url.clone();
         let mut changed = false;
         let mut applied_rules = Vec::new();
+        let mut rule_matches = Vec::new();
+
+        if let Some((origin, proxies)) = unwrapped {
+            debug!("Unwrapped {} from {}", origin, url);
+            url = origin;
+            changed = true;
+            for name in proxies {
+                applied_rules.push(name.to_string());
+                rule_matches.push(RuleMatch {
+                    provider: "proxy".to_string(),
+                    kind: RuleKind::Redirect,
+                    pattern: name.to_string(),
+                    source: "built-in".to_string(),
+                    decoding: Vec::new(),
+                });
+            }
+        }
 
         // Apply provider-specific rules
-        for provider in self.providers {
+        for provider in self.rules.providers() {
             if provider.matches_url(&url)? && !provider.matches_exception(&url)? {
                 // Push the matched provider when found
                 applied_rules.push(provider.name.to_string());
 
                 let result = self.apply_provider_rules(provider, &mut url)?;
+                rule_matches.extend(result.rule_matches);
 
                 // Redirect means we're not responsible
                 if result.redirect {
@@ -129,6 +423,10 @@ impl<'a> UrlCleaner<'a> {
                         redirect: true,
                         cancel: false,
                         applied_rules,
+                        rule_matches,
+                        kept_params: Vec::new(),
+                        suspicious_params: Vec::new(),
+                        minimal_form: None,
                     });
                 }
 
@@ -141,6 +439,10 @@ impl<'a> UrlCleaner<'a> {
                         redirect: false,
                         cancel: true,
                         applied_rules,
+                        rule_matches,
+                        kept_params: Vec::new(),
+                        suspicious_params: Vec::new(),
+                        minimal_form: None,
                     });
                 }
 
@@ -151,10 +453,116 @@ impl<'a> UrlCleaner<'a> {
             }
         }
 
+        // Archive and share links stay, with the URL inside them cleaned
+        if self.options.clean_nested
+            && depth > 0
+            && let Some(embedded) = nested::find(&url)
+            && let Ok(inner) = self.clean_url_to_depth(&embedded.inner, depth - 1)
+            && inner.changed
+            && !inner.cancel
+            && let Some(wrapper) = embedded.embed(&url, &inner.url)
+        {
+            debug!("Cleaned {} inside {}", embedded.inner, url);
+            url = wrapper;
+            changed = true;
+            applied_rules.push(format!("nested_{}", embedded.wrapper));
+            applied_rules.extend(inner.applied_rules);
+            rule_matches.extend(inner.rule_matches);
+        }
+
+        // Text directives are for highlighting, but also tell the site what was searched for
+        if self.options.strip_text_fragments
+            && let Some(main) = url.fragment().and_then(fragment::without_directive)
+        {
+            let main = main.to_string();
+            url.set_fragment((!main.is_empty()).then_some(main.as_str()));
+            changed = true;
+            applied_rules.push("text_fragment".to_string());
+            rule_matches.push(RuleMatch {
+                provider: "text_fragment".to_string(),
+                kind: RuleKind::Raw,
+                pattern: ":~:".to_string(),
+                source: "options".to_string(),
+                decoding: Vec::new(),
+            });
+        }
+
         // Apply additional blocked parameters (neat_url style)
-        if self.apply_additional_param_rules(&mut url)? {
+        let fired = self.apply_additional_param_rules(&mut url)?;
+        if !fired.is_empty() {
             changed = true;
             applied_rules.push("additional_params".to_string());
+            rule_matches.extend(fired.into_iter().map(|(kind, rule)| RuleMatch {
+                provider: "additional_params".to_string(),
+                kind,
+                pattern: rule,
+                source: "options".to_string(),
+                decoding: Vec::new(),
+            }));
+        }
+
+        // Score what the rules left, removing it only past the threshold
+        let mut suspicious_params = Vec::new();
+        if self.options.heuristics {
+            suspicious_params = self.apply_heuristics(&mut url);
+            let removed: Vec<&SuspiciousParam> = suspicious_params
+                .iter()
+                .filter(|param| param.removed)
+                .collect();
+            if !removed.is_empty() {
+                changed = true;
+                applied_rules.push("heuristics".to_string());
+                rule_matches.extend(removed.into_iter().map(|param| RuleMatch {
+                    provider: "heuristics".to_string(),
+                    kind: RuleKind::Param,
+                    pattern: param.name.clone(),
+                    source: "options".to_string(),
+                    decoding: Vec::new(),
+                }));
+            }
+        }
+
+        // Strict mode removes whatever isn't allowlisted
+        let mut kept_params = Vec::new();
+        if self.options.mode == CleaningMode::Strict {
+            let removed = self.apply_strict_mode(&mut url, &mut kept_params);
+            if !removed.is_empty() {
+                changed = true;
+                applied_rules.push("strict".to_string());
+                rule_matches.extend(removed.into_iter().map(|name| RuleMatch {
+                    provider: "strict".to_string(),
+                    kind: RuleKind::Param,
+                    pattern: name,
+                    source: "options".to_string(),
+                    decoding: Vec::new(),
+                }));
+            }
+        }
+
+        // Known sites can lose everything but the item's identity
+        let mut minimal_form = None;
+        if self.options.minimal_form
+            && let Some((minimal, site)) = minimal::minimize(&url)
+        {
+            url = minimal;
+            changed = true;
+            minimal_form = Some(site.to_string());
+        }
+
+        // Canonicalize what's left
+        if self.options.canonicalize.is_enabled() {
+            let normalized = canonical::canonicalize(input, &mut url, &self.options.canonicalize);
+            if !normalized.is_empty() {
+                changed = true;
+                applied_rules.extend(normalized.iter().map(|name| name.to_string()));
+                rule_matches.extend(normalized.into_iter().map(|name| RuleMatch {
+                    provider: "canonical".to_string(),
+                    kind: RuleKind::Raw,
+                    pattern: name.to_string(),
+                    source: "options".to_string(),
+                    decoding: Vec::new(),
+                }));
+            }
         }
 
         // Debug logging
@@ -171,9 +579,35 @@ impl<'a> UrlCleaner<'a> {
             redirect: false,
             cancel: false,
             applied_rules,
+            rule_matches,
+            kept_params,
+            suspicious_params,
+            minimal_form,
         })
     }
 
+    /// A key for telling whether URLs point at the same page: the URL cleaned, with redirects followed and
+    /// cleaned in turn, then canonicalized (`Canonicalization::standard()` unless the options set their own).
+    ///
+    /// Also returns the result of cleaning the last redirect target, the cleaned URL the key was made from.
+    pub fn dedup_key(&self, url: &str) -> Result<(String, CleaningResult)> {
+        let mut result = self.clean_url(url)?;
+
+        // Redirect targets come back as they were found, so they go through cleaning too
+        for _ in 0..MAX_REDIRECTS {
+            if !result.redirect {
+                break;
+            }
+            result = self.clean_url(&result.url)?;
+        }
+
+        let mut key = Url::parse(&result.url).context("Failed to parse cleaned URL")?;
+        if !self.options.canonicalize.is_enabled() {
+            canonical::canonicalize(&result.url, &mut key, &Canonicalization::standard());
+        }
+        Ok((key.to_string(), result))
+    }
+
     /// Determine if we should skip a URL
     fn should_skip_url(&self, url: &Url) -> bool {
         // Skip localhost if configured
@@ -210,6 +644,7 @@ impl<'a> UrlCleaner<'a> {
     fn apply_provider_rules(&self, provider: &Provider, url: &mut Url) -> Result<CleaningResult> {
         let mut changed = false;
         let mut applied_rules = Vec::new();
+        let mut rule_matches = Vec::new();
 
         // Check for cancellation (complete provider blocking)
         if provider.complete_provider && self.options.domain_blocking {
@@ -219,11 +654,21 @@ impl<'a> UrlCleaner<'a> {
                 redirect: false,
                 cancel: true,
                 applied_rules: vec![provider.name.to_string()],
+                rule_matches: vec![RuleMatch::new(
+                    provider,
+                    RuleKind::Cancel,
+                    &provider.url_pattern,
+                )],
+                kept_params: Vec::new(),
+                suspicious_params: Vec::new(),
+                minimal_form: None,
             });
         }
 
         // Check for redirections
-        if let Some(redirect_url) = self.apply_redirections(provider, url)? {
+        if let Some((redirect_url, redirection, decoding)) =
+            self.apply_redirections(provider, url)?
+        {
             *url = redirect_url;
             return Ok(CleaningResult {
                 url: url.to_string(),
@@ -231,24 +676,34 @@ impl<'a> UrlCleaner<'a> {
                 redirect: true,
                 cancel: false,
                 applied_rules: vec![format!("{}_redirect", provider.name)],
+                rule_matches: vec![RuleMatch {
+                    decoding: decoding.into_iter().map(str::to_string).collect(),
+                    ..RuleMatch::new(provider, RuleKind::Redirect, redirection)
+                }],
+                kept_params: Vec::new(),
+                suspicious_params: Vec::new(),
+                minimal_form: None,
             });
         }
 
         // Apply raw rules (regex replacements on the entire URL)
         for (i, raw_rule) in provider.raw_rules.iter().enumerate() {
             let original = url.to_string();
-            let cleaned = raw_rule.replace_all(&original, "");
+            let cleaned = raw_rule.pattern.replace_all(&original, "");
             if cleaned != original {
                 *url = Url::parse(&cleaned).context("Invalid URL after applying raw rule")?;
                 changed = true;
                 applied_rules.push(format!("{}_raw_{}", provider.name, i));
+                rule_matches.push(RuleMatch::new(provider, RuleKind::Raw, raw_rule));
                 debug!("Applied raw rule {} to {}", i, provider.name);
             }
         }
 
         // Apply parameter rules
-        if self.apply_parameter_rules(provider, url)? {
+        let param_matches = self.apply_parameter_rules(provider, url)?;
+        if !param_matches.is_empty() {
             changed = true;
+            rule_matches.extend(param_matches);
         }
 
         Ok(CleaningResult {
@@ -257,55 +712,61 @@ impl<'a> UrlCleaner<'a> {
             redirect: false,
             cancel: false,
             applied_rules,
+            rule_matches,
+            kept_params: Vec::new(),
+            suspicious_params: Vec::new(),
+            minimal_form: None,
         })
     }
 
-    /// Resolve the redirections
-    fn apply_redirections(&self, provider: &Provider, url: &Url) -> Result<Option<Url>> {
-        for &redirection in provider.redirections.iter() {
-            if let Some(captures) = redirection.captures(url.as_str()) {
-                if let Some(redirect_match) = captures.get(1) {
-                    let decoded_url = urlencoding::decode(redirect_match.as_str())
-                        .context("Failed to decode redirect URL")?;
-                    let redirect_url = Url::parse(&decoded_url).context("Invalid redirect URL")?;
-                    debug!("Found redirection: {} -> {}", url, redirect_url);
-                    return Ok(Some(redirect_url));
-                }
+    /// Resolve the redirections, returning the target along with the redirection that produced it and the
+    /// steps that decoded it
+    fn apply_redirections<'p>(
+        &self,
+        provider: &'p Provider,
+        url: &Url,
+    ) -> Result<Option<(Url, &'p Rule, Vec<&'static str>)>> {
+        for redirection in provider.redirections.iter() {
+            if let Some(redirect_match) = redirection.pattern.capture(url.as_str(), 1) {
+                let (redirect_url, decoding) = decode::redirect_target(redirect_match)?;
+                debug!(
+                    "Found redirection: {} -> {} ({:?})",
+                    url, redirect_url, decoding
+                );
+                return Ok(Some((redirect_url, redirection, decoding)));
             }
         }
         Ok(None)
     }
 
-    /// Apply the specific parameter rules (the most complex of them)
-    fn apply_parameter_rules(&self, provider: &Provider, url: &mut Url) -> Result<bool> {
-        let mut changed = false;
+    /// Apply the specific parameter rules (the most complex of them), returning the rules that removed something
+    fn apply_parameter_rules(&self, provider: &Provider, url: &mut Url) -> Result<Vec<RuleMatch>> {
+        let mut rule_matches = Vec::new();
 
         // Collect all rules to apply
-        let all_rules: &Vec<&'static Regex> = provider.rules.as_ref();
+        let all_rules: &Vec<Rule> = &provider.rules;
         // if self.options.apply_referral_marketing {
         //     all_rules.extend(provider.referral_marketing.iter());
         // }
 
         // Remove matching parameters.
         // We only need the key, because that's what the dataset is based on.
+        let host = url.host_str().unwrap_or_default().to_string();
         let params_to_remove: Vec<String> = url
             .query_pairs()
-            .filter_map(|(key, _)| {
-                for &rule in all_rules.iter() {
-                    // Match verbatim keys
-                    let rule = RegexBuilder::new(&format!("^{}$", rule))
-                        .case_insensitive(true)
-                        .build().expect("We're taking an existing regex and making it only match verbatim, shouldn't fail.");
-
-                    if rule.is_match(&key) {
-                        debug!(
-                            "Parameter '{}' matches rule in provider {}",
-                            key, provider.name
-                        );
-                        return Some(key.to_string());
-                    }
+            .filter_map(|(key, value)| {
+                // `!` rules in the options protect a parameter from every provider
+                if self.is_kept(&host, &key) {
+                    return None;
                 }
-                None
+
+                let rule_match = param_rule_match(provider, all_rules, &key, &value)?;
+                debug!(
+                    "Parameter '{}' matches rule in provider {}",
+                    key, provider.name
+                );
+                rule_matches.push(rule_match);
+                Some(key.to_string())
             })
             .collect();
 
@@ -329,67 +790,261 @@ impl<'a> UrlCleaner<'a> {
                     .join("&");
                 url.set_query(Some(&query_string));
             }
+        }
 
-            changed = true;
+        if self.options.clean_fragments {
+            rule_matches.extend(self.apply_fragment_rules(provider, &host, url));
         }
 
-        Ok(changed)
+        Ok(rule_matches)
     }
 
-    /// Apply any additional rules that the input specifies
-    fn apply_additional_param_rules(&self, url: &mut Url) -> Result<bool> {
-        if self.options.additional_blocked_params.is_empty() {
-            return Ok(false);
+    /// Apply the parameter rules to the parameters in the fragment, returning the rules that removed something
+    fn apply_fragment_rules(
+        &self,
+        provider: &Provider,
+        host: &str,
+        url: &mut Url,
+    ) -> Vec<RuleMatch> {
+        let Some(raw) = url.fragment().map(str::to_string) else {
+            return Vec::new();
+        };
+        let Some(parsed) = fragment::Fragment::parse(&raw) else {
+            return Vec::new();
+        };
+
+        let mut rule_matches = Vec::new();
+        let kept: Vec<&str> = parsed
+            .params
+            .iter()
+            .copied()
+            .filter(|param| {
+                let (key, value) = fragment::pair(param);
+                if self.is_kept(host, &key) {
+                    return true;
+                }
+
+                match param_rule_match(provider, &provider.rules, &key, &value) {
+                    Some(rule_match) => {
+                        debug!(
+                            "Fragment parameter '{}' matches rule in provider {}",
+                            key, provider.name
+                        );
+                        rule_matches.push(rule_match);
+                        false
+                    }
+                    None => true,
+                }
+            })
+            .collect();
+
+        if !rule_matches.is_empty() {
+            let rebuilt = parsed.rebuild(&kept);
+            url.set_fragment((!rebuilt.is_empty()).then_some(rebuilt.as_str()));
         }
 
-        // Refer to apply_param_rules for notes on this logic
-        let params_to_remove: HashSet<String> = self
-            .options
-            .additional_blocked_params
+        rule_matches
+    }
+
+    /// Whether a `!` rule in the options keeps this parameter on this host
+    fn is_kept(&self, host: &str, key: &str) -> bool {
+        self.kept_by(host, key).is_some()
+    }
+
+    /// The `!` rule in the options keeping this parameter on this host, if there is one
+    fn kept_by(&self, host: &str, key: &str) -> Option<&NeatRule> {
+        self.neat_rules
             .iter()
-            .cloned()
+            .find(|rule| rule.exclude && rule.applies_to(host) && rule.matches_param(key))
+    }
+
+    /// Score the query parameters that aren't allowlisted or excluded, removing the ones reaching the threshold
+    fn apply_heuristics(&self, url: &mut Url) -> Vec<SuspiciousParam> {
+        let host = url.host_str().unwrap_or_default().to_string();
+        let mut suspicious: Vec<SuspiciousParam> = Vec::new();
+
+        let new_params: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(key, value)| {
+                if self.allowlist.find(&host, key).is_some() || self.is_kept(&host, key) {
+                    return true;
+                }
+
+                let scored = heuristics::score(key, value);
+                if scored.score < SUSPICIOUS {
+                    return true;
+                }
+                let removed = self
+                    .options
+                    .heuristic_threshold
+                    .is_some_and(|threshold| scored.score >= threshold);
+                debug!(
+                    "Parameter '{}' scores {} ({})",
+                    key,
+                    scored.score,
+                    scored.reasons.join(", ")
+                );
+
+                // Repeated parameters are reported once, with their highest score
+                match suspicious.iter_mut().find(|param| param.name == *key) {
+                    Some(param) if param.score >= scored.score => {}
+                    Some(param) => {
+                        param.score = scored.score;
+                        param.reasons = scored.reasons.iter().map(ToString::to_string).collect();
+                        param.removed |= removed;
+                    }
+                    None => suspicious.push(SuspiciousParam {
+                        name: key.to_string(),
+                        score: scored.score,
+                        reasons: scored.reasons.iter().map(ToString::to_string).collect(),
+                        removed,
+                    }),
+                }
+                !removed
+            })
+            .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect();
 
-        let original_count = url.query_pairs().count();
+        if suspicious.iter().any(|param| param.removed) {
+            set_query_pairs(url, &new_params);
+        }
+
+        suspicious
+    }
+
+    /// Remove every query parameter that isn't allowlisted or excluded, returning the names that were removed
+    fn apply_strict_mode(&self, url: &mut Url, kept_params: &mut Vec<KeptParam>) -> Vec<String> {
+        let host = url.host_str().unwrap_or_default().to_string();
+        let mut removed = Vec::new();
 
         let new_params: Vec<(String, String)> = url
             .query_pairs()
-            .filter(|(key, _)| !params_to_remove.contains(key.as_ref()))
+            .filter(|(key, _)| {
+                let kept = match self.allowlist.find(&host, key) {
+                    Some(entry) => Some((entry.rule.source.clone(), entry.source)),
+                    None => self
+                        .kept_by(&host, key)
+                        .map(|rule| (rule.source.clone(), "options")),
+                };
+
+                match kept {
+                    Some((rule, source)) => {
+                        if !kept_params.iter().any(|kept| kept.name == *key) {
+                            kept_params.push(KeptParam {
+                                name: key.to_string(),
+                                rule,
+                                source: source.to_string(),
+                            });
+                        }
+                        true
+                    }
+                    None => {
+                        debug!("Strict mode removes parameter '{}'", key);
+                        if !removed.contains(&key.to_string()) {
+                            removed.push(key.to_string());
+                        }
+                        false
+                    }
+                }
+            })
             .map(|(k, v)| (k.to_string(), v.to_string()))
             .collect();
 
-        if new_params.len() != original_count {
-            if new_params.is_empty() {
-                url.set_query(None);
-            } else {
-                let query_string = new_params
-                    .iter()
-                    .map(|(k, v)| format!("{}={}", k, v))
-                    .collect::<Vec<_>>()
-                    .join("&");
-                url.set_query(Some(&query_string));
+        if !removed.is_empty() {
+            set_query_pairs(url, &new_params);
+        }
+
+        removed
+    }
+
+    /// Apply the Neat URL rules from the options, returning the rules that changed something
+    fn apply_additional_param_rules(&self, url: &mut Url) -> Result<Vec<(RuleKind, String)>> {
+        let host = url.host_str().unwrap_or_default().to_string();
+        let rules: Vec<&NeatRule> = self
+            .neat_rules
+            .iter()
+            .filter(|rule| !rule.exclude && rule.applies_to(&host))
+            .collect();
+
+        let mut fired = Vec::new();
+
+        // `$` and `$$` rules cut the URL down
+        for rule in &rules {
+            if rule
+                .cut(url)
+                .context("Invalid URL after applying a Neat URL rule")?
+            {
+                fired.push((RuleKind::Raw, rule.source.clone()));
             }
-            return Ok(true);
         }
 
-        Ok(false)
+        // Refer to apply_param_rules for notes on this logic
+        let mut removed = false;
+        let new_params: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(key, _)| {
+                let rule = rules.iter().find(|rule| rule.matches_param(key));
+                match rule {
+                    Some(rule) if !self.is_kept(&host, key) => {
+                        let fired_rule = (RuleKind::Param, rule.source.clone());
+                        if !fired.contains(&fired_rule) {
+                            fired.push(fired_rule);
+                        }
+                        removed = true;
+                        false
+                    }
+                    _ => true,
+                }
+            })
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+
+        if removed {
+            set_query_pairs(url, &new_params);
+        }
+
+        Ok(fired)
     }
 }
 
-impl Provider {
-    fn matches_url(&self, url: &Url) -> Result<bool> {
-        Ok(self.url_pattern.is_match(url.as_str()))
+/// Replace the query string with these parameters, dropping it when there are none
+pub(crate) fn set_query_pairs(url: &mut Url, params: &[(String, String)]) {
+    if params.is_empty() {
+        url.set_query(None);
+    } else {
+        let query_string = params
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+        url.set_query(Some(&query_string));
     }
+}
 
-    fn matches_exception(&self, url: &Url) -> Result<bool> {
-        for exception in self.exceptions.iter() {
-            if exception.is_match(url.as_str()) {
-                debug!("URL {} matches exception in provider {}", url, self.name);
-                return Ok(true);
-            }
-        }
-        Ok(false)
+/// The first of the provider's parameter rules that removes this parameter: a key rule, or a value rule
+/// matching both the key and the value
+fn param_rule_match(
+    provider: &Provider,
+    rules: &[Rule],
+    key: &str,
+    value: &str,
+) -> Option<RuleMatch> {
+    // Parameter rules are compiled to match verbatim keys, case insensitively
+    if let Some(rule) = rules.iter().find(|rule| rule.is_match(key)) {
+        return Some(RuleMatch::new(provider, RuleKind::Param, rule));
     }
+
+    let rule = provider
+        .value_rules
+        .iter()
+        .find(|rule| rule.is_match(key, value))?;
+    Some(RuleMatch {
+        provider: provider.name.clone(),
+        kind: RuleKind::Param,
+        pattern: rule.pattern(),
+        source: rule.key.source.to_string(),
+        decoding: Vec::new(),
+    })
 }
 
 #[cfg(test)]
@@ -422,4 +1077,346 @@ mod tests {
         assert!(result.changed);
         assert_eq!(result.url.as_str(), "https://example.com/?test=1");
     }
+
+    #[test]
+    fn test_neat_url_options() {
+        let options = CleaningOptions {
+            additional_blocked_params: vec![
+                "ref@*.shop.test".to_string(),
+                "trk_*".to_string(),
+                "$$".to_string(),
+                "!utm_source@partner.test".to_string(),
+            ],
+            ..Default::default()
+        };
+
+        let cleaner = UrlCleaner::new(options).unwrap();
+        let clean = |url: &str| cleaner.clean_url(url).unwrap().url;
+
+        assert_eq!(
+            clean("https://www.shop.test/item?id=1&ref=2&trk_a=3#top"),
+            "https://www.shop.test/item?id=1"
+        );
+        assert_eq!(
+            clean("https://blog.test/?ref=2"),
+            "https://blog.test/?ref=2"
+        );
+
+        // Exclusions also protect parameters from the provider rules
+        assert_eq!(
+            clean("https://partner.test/?utm_source=x&utm_medium=y"),
+            "https://partner.test/?utm_source=x"
+        );
+
+        let result = cleaner.clean_url("https://www.shop.test/?ref=2").unwrap();
+        assert_eq!(result.rule_matches[0].pattern, "ref@*.shop.test");
+    }
+
+    #[test]
+    fn test_fragment_cleaning() {
+        let cleaner = UrlCleaner::new(CleaningOptions::default()).unwrap();
+        let clean = |url: &str| cleaner.clean_url(url).unwrap().url;
+
+        assert_eq!(
+            clean("https://app.test/#/inbox?id=4&utm_source=mail"),
+            "https://app.test/#/inbox?id=4"
+        );
+        assert_eq!(
+            clean("https://app.test/#/inbox?utm_source=mail"),
+            "https://app.test/#/inbox"
+        );
+        assert_eq!(
+            clean("https://news.test/a#utm_medium=rss"),
+            "https://news.test/a"
+        );
+        assert_eq!(
+            clean("https://news.test/a#section-2"),
+            "https://news.test/a#section-2"
+        );
+        assert_eq!(
+            clean("https://news.test/a#top:~:text=quote"),
+            "https://news.test/a#top:~:text=quote"
+        );
+
+        let options = CleaningOptions {
+            strip_text_fragments: true,
+            ..Default::default()
+        };
+        let cleaner = UrlCleaner::new(options).unwrap();
+        let result = cleaner
+            .clean_url("https://news.test/a#top:~:text=quote")
+            .unwrap();
+        assert_eq!(result.url, "https://news.test/a#top");
+        assert!(result.applied_rules.contains(&"text_fragment".to_string()));
+        assert_eq!(
+            cleaner
+                .clean_url("https://news.test/a#:~:text=quote")
+                .unwrap()
+                .url,
+            "https://news.test/a"
+        );
+    }
+
+    #[test]
+    fn test_value_rules() {
+        let file = RuleFile::from_toml(
+            r#"
+            [providers.shop]
+            domains = ["shop.test"]
+            params = ["tag"]
+
+            [[providers.shop.value_rules]]
+            key = "ref"
+            value = "[A-Z0-9]{10}"
+
+            [[providers.shop.value_rules]]
+            key = "source"
+            value = "email"
+            "#,
+        )
+        .unwrap();
+        let rules = RuleSet::from_file(&file, RuleSource::User("shop.toml".into())).unwrap();
+        let cleaner = UrlCleaner::with_rules(CleaningOptions::default(), &rules).unwrap();
+        let clean = |url: &str| cleaner.clean_url(url).unwrap().url;
+
+        assert_eq!(
+            clean("https://shop.test/item?ref=B00AFF1X2Z&source=email&tag=1"),
+            "https://shop.test/item"
+        );
+        assert_eq!(
+            clean("https://shop.test/item?ref=main&source=emails"),
+            "https://shop.test/item?ref=main&source=emails"
+        );
+        assert_eq!(
+            clean("https://shop.test/#/item?id=1&source=email"),
+            "https://shop.test/#/item?id=1"
+        );
+
+        let result = cleaner
+            .clean_url("https://shop.test/item?ref=B00AFF1X2Z")
+            .unwrap();
+        assert_eq!(result.rule_matches[0].pattern, "ref=[A-Z0-9]{10}");
+        assert_eq!(result.rule_matches[0].source, "shop.toml");
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        let options = CleaningOptions {
+            mode: CleaningMode::Strict,
+            allowed_params: vec!["page@blog.test".to_string()],
+            additional_blocked_params: vec!["!lang".to_string()],
+            ..Default::default()
+        };
+        let cleaner = UrlCleaner::new(options).unwrap();
+
+        let result = cleaner
+            .clean_url("https://www.youtube.com/watch?v=abc&ab_channel=x&t=42&lang=en")
+            .unwrap();
+        assert_eq!(
+            result.url,
+            "https://www.youtube.com/watch?v=abc&t=42&lang=en"
+        );
+        assert_eq!(
+            result.kept_params[0],
+            KeptParam {
+                name: "v".to_string(),
+                rule: "v@*.youtube.com".to_string(),
+                source: "built-in".to_string(),
+            }
+        );
+        assert_eq!(result.kept_params[2].rule, "!lang");
+        assert!(
+            result
+                .rule_matches
+                .iter()
+                .any(|m| m.provider == "strict" && m.pattern == "ab_channel")
+        );
+
+        assert_eq!(
+            cleaner
+                .clean_url("https://blog.test/post?page=2&from=home")
+                .unwrap()
+                .url,
+            "https://blog.test/post?page=2"
+        );
+        assert_eq!(
+            cleaner.clean_url("https://other.test/?page=2").unwrap().url,
+            "https://other.test/"
+        );
+    }
+
+    #[test]
+    fn test_heuristics() {
+        let url = "https://shop.test/item?id=7&visitor=p2ANqtz-8Fh3kQx9LmZr4VwYbN0cTgHd&trk_campaign=spring";
+
+        let options = CleaningOptions {
+            heuristics: true,
+            ..Default::default()
+        };
+        let result = UrlCleaner::new(options).unwrap().clean_url(url).unwrap();
+        assert!(!result.changed);
+        let scores: Vec<(&str, f64)> = result
+            .suspicious_params
+            .iter()
+            .map(|param| (param.name.as_str(), param.score))
+            .collect();
+        assert_eq!(scores, vec![("visitor", 0.55), ("trk_campaign", 0.45)]);
+
+        let options = CleaningOptions {
+            heuristics: true,
+            heuristic_threshold: Some(0.5),
+            ..Default::default()
+        };
+        let result = UrlCleaner::new(options).unwrap().clean_url(url).unwrap();
+        assert_eq!(
+            result.url,
+            "https://shop.test/item?id=7&trk_campaign=spring"
+        );
+        assert!(result.suspicious_params[0].removed);
+        assert!(result.applied_rules.contains(&"heuristics".to_string()));
+    }
+
+    #[test]
+    fn test_encoded_redirects() {
+        let file = RuleFile::from_json(
+            r#"{"providers": {
+                "bing": {
+                    "domains": ["bing.com"],
+                    "redirections": ["^https?:\\/\\/(?:www\\.)?bing\\.com\\/ck\\/a\\?.*?&u=([^&]+)"]
+                },
+                "yahoo": {
+                    "domains": ["r.search.yahoo.com"],
+                    "redirections": ["^https?:\\/\\/r\\.search\\.yahoo\\.com\\/.*?\\/RU=([^/]+)\\/RK="]
+                },
+                "newsletter": {
+                    "domains": ["click.mail.test"],
+                    "redirections": ["^https?:\\/\\/click\\.mail\\.test\\/\\?target=([^&]+)"]
+                }
+            }}"#,
+        )
+        .unwrap();
+        let rules = RuleSet::from_file(&file, RuleSource::BuiltIn).unwrap();
+        let cleaner = UrlCleaner::with_rules(CleaningOptions::default(), &rules).unwrap();
+
+        let result = cleaner
+            .clean_url("https://www.bing.com/ck/a?!&&p=abc&u=a1aHR0cHM6Ly9leGFtcGxlLmNvbS9wYWdlP2lkPTE&ntb=1")
+            .unwrap();
+        assert!(result.redirect);
+        assert_eq!(result.url, "https://example.com/page?id=1");
+        assert_eq!(result.rule_matches[0].decoding, vec!["prefixed_base64"]);
+
+        // Yahoo puts the target in a path segment, with lower case escapes
+        let result = cleaner
+            .clean_url("https://r.search.yahoo.com/_ylt=AwrE1x;_ylu=Y29sbwM-/RV=2/RE=1700000000/RO=10/RU=https%3a%2f%2fexample.com%2fpage%3fid%3d1/RK=2/RS=abc-")
+            .unwrap();
+        assert!(result.redirect);
+        assert_eq!(result.url, "https://example.com/page?id=1");
+        assert_eq!(result.rule_matches[0].decoding, vec!["percent"]);
+
+        // Percent-encoded twice, with HTML escapes inside
+        let result = cleaner
+            .clean_url("https://click.mail.test/?target=https%253A%252F%252Fexample.com%252F%253Fa%253D1%2526amp%253Bb%253D2")
+            .unwrap();
+        assert_eq!(result.url, "https://example.com/?a=1&b=2");
+        assert_eq!(
+            result.rule_matches[0].decoding,
+            vec!["percent", "percent", "html_entities"]
+        );
+    }
+
+    #[test]
+    fn test_proxy_unwrapping() {
+        let file = RuleFile::from_json(
+            r#"{"providers": {"globalRules": {"urlPattern": ".*", "rules": ["utm_source"]}}}"#,
+        )
+        .unwrap();
+        let rules = RuleSet::from_file(&file, RuleSource::BuiltIn).unwrap();
+        let url = "https://www.google.com/amp/s/example.com/a?utm_source=x";
+
+        let cleaner = UrlCleaner::with_rules(CleaningOptions::default(), &rules).unwrap();
+        let result = cleaner.clean_url(url).unwrap();
+        assert_eq!(result.url, "https://example.com/a");
+        assert_eq!(result.applied_rules, vec!["google_amp", "globalRules"]);
+
+        // The origin is blacklisted even though the wrapper isn't
+        let options = CleaningOptions {
+            blacklisted_domains: vec!["example.com".to_string()],
+            ..CleaningOptions::default()
+        };
+        let cleaner = UrlCleaner::with_rules(options, &rules).unwrap();
+        let result = cleaner.clean_url(url).unwrap();
+        assert!(!result.changed);
+        assert_eq!(result.url, url);
+    }
+
+    #[test]
+    fn test_dedup_key() {
+        let file = RuleFile::from_json(
+            r#"{"providers": {
+                "globalRules": {"urlPattern": ".*", "rules": ["utm_source"]},
+                "out": {
+                    "domains": ["out.test"],
+                    "redirections": ["^https?:\\/\\/out\\.test\\/\\?to=([^&]+)"]
+                }
+            }}"#,
+        )
+        .unwrap();
+        let rules = RuleSet::from_file(&file, RuleSource::BuiltIn).unwrap();
+        let cleaner = UrlCleaner::with_rules(CleaningOptions::default(), &rules).unwrap();
+
+        let keys: Vec<String> = [
+            "https://news.test/a?id=1&lang=en",
+            "https://News.test:443/a?lang=en&utm_source=x&id=1",
+            "https://out.test/?to=https%3A%2F%2Fnews.test%2Fa%3Fid%3D1%26lang%3Den%26utm_source%3Dy",
+        ]
+        .iter()
+        .map(|url| cleaner.dedup_key(url).unwrap().0)
+        .collect();
+
+        assert_eq!(keys[0], "https://news.test/a?id=1&lang=en");
+        assert!(keys.iter().all(|key| *key == keys[0]), "{:?}", keys);
+        assert_ne!(
+            cleaner.dedup_key("https://news.test/a?id=2").unwrap().0,
+            keys[0]
+        );
+
+        // The result is the redirect target cleaned, not the target as the redirect had it
+        let (_, result) = cleaner
+            .dedup_key("https://out.test/?to=https%3A%2F%2Fnews.test%2Fa%3Fid%3D1%26utm_source%3Dy")
+            .unwrap();
+        assert_eq!(result.url, "https://news.test/a?id=1");
+    }
+
+    #[test]
+    fn test_user_rules_carry_source() {
+        let overlay = RuleFile::from_toml(
+            r#"
+            [providers.google]
+            params = ["internal_ref"]
+            "#,
+        )
+        .unwrap();
+
+        let mut rules = RuleSet::embedded().clone();
+        rules
+            .merge(&overlay, RuleSource::User("company.toml".into()))
+            .unwrap();
+
+        let cleaner = UrlCleaner::with_rules(CleaningOptions::default(), &rules).unwrap();
+        let result = cleaner
+            .clean_url("https://google.com/search?q=test&internal_ref=abc&utm_source=x")
+            .unwrap();
+
+        assert_eq!(result.url.as_str(), "https://google.com/search?q=test");
+
+        let internal = result
+            .rule_matches
+            .iter()
+            .find(|m| m.pattern == "internal_ref")
+            .unwrap();
+        assert_eq!(internal.provider, "google");
+        assert_eq!(internal.source, "company.toml");
+
+        assert!(result.rule_matches.iter().any(|m| m.source == "built-in"));
+    }
 }
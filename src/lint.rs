@@ -0,0 +1,652 @@
+//! Static checks over ClearURLs style rulesets.
+//!
+//! The rules are written for JavaScript's regex engine, so a pattern that Rust's `regex` rejects would
+//! otherwise only surface as a skipped rule when the ruleset is loaded. The linter compiles every pattern up
+//! front, the same way the runtime does, and looks for providers and rules that can never do anything useful.
+
+use std::{fmt, fs, path::Path};
+
+use anyhow::{Context, Result};
+use regex_syntax::hir::{Class, Hir, HirKind, Look};
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{MapAccess, Visitor},
+};
+
+use crate::{
+    Pattern, ProviderSpec, RuleFile,
+    js_regex::{self, Usage},
+};
+
+/// An unrelated URL that only an overly broad urlPattern would match
+const PROBE_URL: &str = "https://plink-lint.invalid/probe/path?probe=1";
+/// An unrelated parameter name that only an overly broad rule would match
+const PROBE_PARAM: &str = "plinklintprobe";
+
+/// How serious a lint finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// The check that produced a finding
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintKind {
+    /// The provider doesn't fit the ClearURLs schema
+    InvalidProvider,
+    /// The pattern can't be compiled, even after translating it from JavaScript syntax
+    InvalidRegex,
+    /// The pattern uses JavaScript-only syntax that had to be rewritten
+    JsSyntax,
+    /// The pattern only runs on the (optional) backtracking engine
+    Backtracking,
+    /// The same provider name appears more than once
+    DuplicateProvider,
+    /// The same pattern appears twice in one provider
+    DuplicateRule,
+    /// Another provider has the exact same urlPattern, or blocks every URL this one handles
+    ShadowedProvider,
+    /// A URL level rule can't match anything the provider's urlPattern matches
+    UnreachableRule,
+    /// An exception covers every URL the provider matches
+    DeadProvider,
+    /// The pattern matches far more than it's likely meant to
+    BroadPattern,
+}
+
+impl fmt::Display for LintKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = serde_json::to_value(self)
+            .ok()
+            .and_then(|value| value.as_str().map(str::to_string))
+            .unwrap_or_default();
+        write!(f, "{}", name)
+    }
+}
+
+/// A single finding
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LintIssue {
+    pub severity: Severity,
+    pub kind: LintKind,
+    /// Provider the finding is about
+    pub provider: String,
+    /// Where in the provider, e.g. `rules[3]`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}] {}", self.severity, self.kind, self.provider)?;
+        if let Some(field) = &self.field {
+            write!(f, ".{}", field)?;
+        }
+        if let Some(pattern) = &self.pattern {
+            write!(f, " `{}`", pattern)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Everything the linter found in one ruleset
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LintReport {
+    /// The file that was linted, or `built-in`
+    pub source: String,
+    pub providers: usize,
+    pub patterns: usize,
+    pub issues: Vec<LintIssue>,
+}
+
+impl LintReport {
+    pub fn errors(&self) -> usize {
+        self.count(Severity::Error)
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.count(Severity::Warning)
+    }
+
+    fn count(&self, severity: Severity) -> usize {
+        self.issues
+            .iter()
+            .filter(|issue| issue.severity == severity)
+            .count()
+    }
+}
+
+/// Lint a rule file on disk, JSON unless it ends in `.toml`
+pub fn lint_path(path: &Path) -> Result<LintReport> {
+    let contents = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read rule file {}", path.display()))?;
+
+    let is_toml = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+
+    let mut report = if is_toml {
+        lint_file(&RuleFile::from_toml(&contents)?)
+    } else {
+        lint_json(&contents)?
+    };
+
+    report.source = path.display().to_string();
+    Ok(report)
+}
+
+/// Lint a ruleset in the ClearURLs JSON schema.
+///
+/// Unlike going through `RuleFile`, this sees providers that are defined more than once.
+pub fn lint_json(json: &str) -> Result<LintReport> {
+    let raw: RawRuleFile = serde_json::from_str(json).context("Failed to parse JSON rule file")?;
+
+    let mut issues = Vec::new();
+    let mut providers: Vec<(String, ProviderSpec)> = Vec::new();
+
+    for (name, value) in raw.providers.0 {
+        if providers.iter().any(|(existing, _)| *existing == name) {
+            issues.push(LintIssue {
+                severity: Severity::Error,
+                kind: LintKind::DuplicateProvider,
+                provider: name.clone(),
+                field: None,
+                pattern: None,
+                message: "provider is defined more than once, only the last definition is used"
+                    .to_string(),
+            });
+        }
+
+        match serde_json::from_value::<ProviderSpec>(value) {
+            Ok(spec) => providers.push((name, spec)),
+            Err(e) => issues.push(LintIssue {
+                severity: Severity::Error,
+                kind: LintKind::InvalidProvider,
+                provider: name,
+                field: None,
+                pattern: None,
+                message: e.to_string(),
+            }),
+        }
+    }
+
+    let mut report = lint_providers(&providers);
+    issues.append(&mut report.issues);
+    report.issues = issues;
+    Ok(report)
+}
+
+/// Lint an already parsed rule file
+pub fn lint_file(file: &RuleFile) -> LintReport {
+    let providers: Vec<(String, ProviderSpec)> = file
+        .providers
+        .iter()
+        .map(|(name, spec)| (name.clone(), spec.clone()))
+        .collect();
+
+    lint_providers(&providers)
+}
+
+/// A provider along with its compiled urlPattern, kept around for the cross-provider checks
+struct Compiled<'a> {
+    name: &'a str,
+    spec: &'a ProviderSpec,
+    url_pattern: Option<Pattern>,
+}
+
+/// Collects the findings for one provider
+struct Findings<'a> {
+    provider: &'a str,
+    issues: &'a mut Vec<LintIssue>,
+}
+
+impl Findings<'_> {
+    fn push(
+        &mut self,
+        severity: Severity,
+        kind: LintKind,
+        field: &str,
+        pattern: &str,
+        message: String,
+    ) {
+        self.issues.push(LintIssue {
+            severity,
+            kind,
+            provider: self.provider.to_string(),
+            field: Some(field.to_string()),
+            pattern: Some(pattern.to_string()),
+            message,
+        });
+    }
+
+    /// Compile a pattern the way the runtime does, reporting anything that needed special handling
+    fn compile(&mut self, field: &str, pattern: &str, usage: Usage) -> Option<Pattern> {
+        let translated = js_regex::translate(pattern, usage);
+
+        // Escaped slashes are in almost every ClearURLs pattern and always safe to rewrite
+        let rewrites: Vec<_> = translated
+            .rewrites
+            .iter()
+            .filter(|rewrite| **rewrite != "escaped slash")
+            .copied()
+            .collect();
+        if !rewrites.is_empty() {
+            self.push(
+                Severity::Warning,
+                LintKind::JsSyntax,
+                field,
+                pattern,
+                format!("rewrote JavaScript-only syntax: {}", rewrites.join(", ")),
+            );
+        }
+
+        match Pattern::new(pattern, usage) {
+            Ok(compiled) => {
+                if compiled.is_backtracking() {
+                    self.push(
+                        Severity::Warning,
+                        LintKind::Backtracking,
+                        field,
+                        pattern,
+                        "only runs on the backtracking engine".to_string(),
+                    );
+                }
+                Some(compiled)
+            }
+            Err(e) => {
+                self.push(
+                    Severity::Error,
+                    LintKind::InvalidRegex,
+                    field,
+                    pattern,
+                    compile_error(&e),
+                );
+                None
+            }
+        }
+    }
+}
+
+fn lint_providers(providers: &[(String, ProviderSpec)]) -> LintReport {
+    let mut report = LintReport {
+        source: String::new(),
+        providers: providers.len(),
+        patterns: 0,
+        issues: Vec::new(),
+    };
+
+    let mut compiled = Vec::new();
+
+    for (name, spec) in providers {
+        let url_pattern = spec.url_pattern();
+        if url_pattern.is_none() {
+            report.issues.push(LintIssue {
+                severity: Severity::Error,
+                kind: LintKind::InvalidProvider,
+                provider: name.clone(),
+                field: None,
+                pattern: None,
+                message: "provider has neither a urlPattern nor domains".to_string(),
+            });
+        }
+
+        let mut findings = Findings {
+            provider: name,
+            issues: &mut report.issues,
+        };
+
+        // The provider's own urlPattern
+        let url_pattern = url_pattern.and_then(|pattern| {
+            report.patterns += 1;
+            let compiled = findings.compile("urlPattern", &pattern, Usage::Url)?;
+
+            if pattern != ".*" && compiled.is_match(PROBE_URL) {
+                findings.push(
+                    Severity::Warning,
+                    LintKind::BroadPattern,
+                    "urlPattern",
+                    &pattern,
+                    format!("matches unrelated URLs such as {}", PROBE_URL),
+                );
+            }
+            Some(compiled)
+        });
+
+        // Parameter rules are matched against keys, anchored and case insensitive
+        let param_fields = [
+            ("rules", spec.param_rules()),
+            ("referralMarketing", spec.referral_marketing.clone()),
+        ];
+        for (field, patterns) in &param_fields {
+            for (i, pattern) in patterns.iter().enumerate() {
+                report.patterns += 1;
+                let field = format!("{}[{}]", field, i);
+
+                if patterns[..i].contains(pattern) {
+                    findings.push(
+                        Severity::Warning,
+                        LintKind::DuplicateRule,
+                        &field,
+                        pattern,
+                        "pattern is listed more than once".to_string(),
+                    );
+                }
+
+                let Some(compiled) = findings.compile(&field, pattern, Usage::Key) else {
+                    continue;
+                };
+
+                if compiled.is_match("") || compiled.is_match(PROBE_PARAM) {
+                    findings.push(
+                        Severity::Warning,
+                        LintKind::BroadPattern,
+                        &field,
+                        pattern,
+                        "matches (almost) any parameter name".to_string(),
+                    );
+                }
+            }
+        }
+
+        // Value rules are a key pattern and a value pattern, both matched whole
+        for (i, rule) in spec.value_rules.iter().enumerate() {
+            report.patterns += 1;
+            let field = format!("valueRules[{}]", i);
+            findings.compile(&format!("{}.key", field), &rule.key, Usage::Key);
+            findings.compile(&format!("{}.value", field), &rule.value, Usage::Value);
+        }
+
+        // URL level rules are matched against the whole URL
+        let url_fields = [
+            ("rawRules", &spec.raw_rules, Usage::Replace),
+            ("exceptions", &spec.exceptions, Usage::Url),
+            ("redirections", &spec.redirections, Usage::Url),
+        ];
+        for (field, patterns, usage) in url_fields {
+            for (i, pattern) in patterns.iter().enumerate() {
+                report.patterns += 1;
+                let field = format!("{}[{}]", field, i);
+
+                if patterns[..i].contains(pattern) {
+                    findings.push(
+                        Severity::Warning,
+                        LintKind::DuplicateRule,
+                        &field,
+                        pattern,
+                        "pattern is listed more than once".to_string(),
+                    );
+                }
+
+                let Some(compiled) = findings.compile(&field, pattern, usage) else {
+                    continue;
+                };
+
+                if compiled.is_match("") {
+                    findings.push(
+                        Severity::Warning,
+                        LintKind::BroadPattern,
+                        &field,
+                        pattern,
+                        "matches every URL".to_string(),
+                    );
+                }
+
+                // A rule anchored at the start of the URL has to agree with the urlPattern on the host
+                if let Some(url_pattern) = &url_pattern
+                    && let Some(example) = anchored_witness(pattern)
+                    && !url_pattern.is_match(&example)
+                {
+                    findings.push(
+                        Severity::Warning,
+                        LintKind::UnreachableRule,
+                        &field,
+                        pattern,
+                        format!(
+                            "URLs like {} match this rule but not the provider's urlPattern",
+                            example
+                        ),
+                    );
+                }
+
+                if field.starts_with("exceptions")
+                    && let Some(url_pattern) = &url_pattern
+                    && covers(&compiled, url_pattern.as_str())
+                {
+                    findings.push(
+                        Severity::Warning,
+                        LintKind::DeadProvider,
+                        &field,
+                        pattern,
+                        "exception matches every URL the provider applies to".to_string(),
+                    );
+                }
+            }
+        }
+
+        compiled.push(Compiled {
+            name,
+            spec,
+            url_pattern,
+        });
+    }
+
+    report.issues.extend(cross_provider_issues(&compiled));
+    report
+}
+
+/// Checks between providers: identical urlPatterns, and providers that a complete provider always blocks first
+fn cross_provider_issues(compiled: &[Compiled]) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    for (i, provider) in compiled.iter().enumerate() {
+        let Some(url_pattern) = &provider.url_pattern else {
+            continue;
+        };
+
+        for other in &compiled[..i] {
+            let Some(other_pattern) = &other.url_pattern else {
+                continue;
+            };
+
+            if url_pattern.as_str() == other_pattern.as_str() && other.name != provider.name {
+                issues.push(LintIssue {
+                    severity: Severity::Warning,
+                    kind: LintKind::ShadowedProvider,
+                    provider: provider.name.to_string(),
+                    field: Some("urlPattern".to_string()),
+                    pattern: Some(url_pattern.as_str().to_string()),
+                    message: format!("same urlPattern as provider '{}'", other.name),
+                });
+            }
+        }
+
+        for other in compiled {
+            let Some(other_pattern) = &other.url_pattern else {
+                continue;
+            };
+
+            let blocks = other.spec.complete_provider.unwrap_or(false)
+                && !provider.spec.complete_provider.unwrap_or(false)
+                && other.name != provider.name
+                && other_pattern.as_str() != ".*";
+
+            if blocks && covers(other_pattern, url_pattern.as_str()) {
+                issues.push(LintIssue {
+                    severity: Severity::Warning,
+                    kind: LintKind::ShadowedProvider,
+                    provider: provider.name.to_string(),
+                    field: None,
+                    pattern: None,
+                    message: format!(
+                        "complete provider '{}' blocks every URL this provider applies to",
+                        other.name
+                    ),
+                });
+            }
+        }
+    }
+
+    issues
+}
+
+/// Whether `regex` matches the example URLs generated from `pattern`, a cheap stand-in for "covers"
+fn covers(regex: &Pattern, pattern: &str) -> bool {
+    if regex.as_str() == pattern {
+        return true;
+    }
+
+    let Some(example) = witness(pattern) else {
+        return false;
+    };
+
+    [
+        example.clone(),
+        format!("{}/plink/lint", example),
+        format!("{}?probe=1", example),
+    ]
+    .iter()
+    .all(|url| regex.is_match(url))
+}
+
+/// Like `witness`, but only for patterns anchored to the start of the URL
+fn anchored_witness(pattern: &str) -> Option<String> {
+    let hir = regex_syntax::parse(&js_regex::translate(pattern, Usage::Replace).pattern).ok()?;
+    if !hir.properties().look_set_prefix().contains(Look::Start) {
+        return None;
+    }
+    let mut out = String::new();
+    build_witness(&hir, &mut out);
+    Some(out)
+}
+
+/// Generate one string the pattern matches, taking the shortest path through it
+fn witness(pattern: &str) -> Option<String> {
+    let hir = regex_syntax::parse(&js_regex::translate(pattern, Usage::Replace).pattern).ok()?;
+    let mut out = String::new();
+    build_witness(&hir, &mut out);
+    Some(out)
+}
+
+fn build_witness(hir: &Hir, out: &mut String) {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => {}
+        HirKind::Literal(literal) => out.push_str(&String::from_utf8_lossy(&literal.0)),
+        HirKind::Class(Class::Unicode(class)) => {
+            // Prefer characters that keep the URL readable (and parseable)
+            let preferred = ['a', '0', '-', '.', '/'];
+            let chosen = preferred
+                .into_iter()
+                .find(|c| {
+                    class
+                        .ranges()
+                        .iter()
+                        .any(|range| range.start() <= *c && *c <= range.end())
+                })
+                .or_else(|| class.ranges().first().map(|range| range.start()));
+            out.extend(chosen);
+        }
+        HirKind::Class(Class::Bytes(class)) => {
+            if let Some(range) = class.ranges().first() {
+                out.push(char::from(range.start()));
+            }
+        }
+        HirKind::Repetition(repetition) => {
+            for _ in 0..repetition.min {
+                build_witness(&repetition.sub, out);
+            }
+        }
+        HirKind::Capture(capture) => build_witness(&capture.sub, out),
+        HirKind::Concat(parts) => parts.iter().for_each(|part| build_witness(part, out)),
+        HirKind::Alternation(alternatives) => {
+            if let Some(first) = alternatives.first() {
+                build_witness(first, out);
+            }
+        }
+    }
+}
+
+fn compile_error(e: &anyhow::Error) -> String {
+    // The full error spans several lines with a caret diagram, the last line is the useful bit
+    format!("{:#}", e)
+        .lines()
+        .rfind(|line| !line.trim().is_empty())
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// A rule file whose providers are kept as an ordered list, so duplicate names survive parsing
+#[derive(Deserialize)]
+struct RawRuleFile {
+    providers: ProviderEntries,
+}
+
+struct ProviderEntries(Vec<(String, serde_json::Value)>);
+
+impl<'de> Deserialize<'de> for ProviderEntries {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntriesVisitor;
+
+        impl<'de> Visitor<'de> for EntriesVisitor {
+            type Value = ProviderEntries;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a map of providers")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = map.next_entry()? {
+                    entries.push(entry);
+                }
+                Ok(ProviderEntries(entries))
+            }
+        }
+
+        deserializer.deserialize_map(EntriesVisitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lint_findings() {
+        let report = lint_json(
+            r#"{"providers": {
+                "shop": {"urlPattern": "^https?:\\/\\/shop\\.test", "rules": ["ref", "(unclosed"],
+                         "exceptions": ["^https?:\\/\\/other\\.test\\/"]},
+                "shop": {"urlPattern": "^https?:\\/\\/shop\\.test", "rules": [".*"],
+                         "exceptions": ["^https?:\\/\\/shop\\.test"]},
+                "ads": {"urlPattern": "^https?:\\/\\/(?:[a-z0-9-]+\\.)*?shop\\.test", "completeProvider": true}
+            }}"#,
+        )
+        .unwrap();
+
+        let kinds: Vec<LintKind> = report.issues.iter().map(|issue| issue.kind).collect();
+
+        assert!(kinds.contains(&LintKind::DuplicateProvider));
+        assert!(kinds.contains(&LintKind::InvalidRegex));
+        assert!(kinds.contains(&LintKind::UnreachableRule));
+        assert!(kinds.contains(&LintKind::BroadPattern));
+        assert!(kinds.contains(&LintKind::DeadProvider));
+        assert!(kinds.contains(&LintKind::ShadowedProvider));
+        assert!(report.errors() >= 2);
+    }
+}
@@ -1,17 +1,56 @@
+use std::path::PathBuf;
+
 use anyhow::Result;
-mod rules;
-use clap::Parser;
-use plink::{CleaningOptions, UrlCleaner};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use plink::{
+    Canonicalization, CleaningMode, CleaningOptions, CleaningResult, RuleSet, RulesetInfo,
+    TrailingSlash, UrlCleaner,
+};
 // use tracing::Level;
 // use tracing_subscriber::{FmtSubscriber, fmt::format::FmtSpan};
 
+mod commands;
+
 /// Simple URL cleaner CLI
 #[derive(Debug, Parser)]
 #[command(
     name = "plink",
-    about = "Clean URL's by peeling away tracking parameters and other junk"
+    about = "Clean URL's by peeling away tracking parameters and other junk",
+    version,
+    disable_version_flag = true,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
 )]
 struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    clean: CleanArgs,
+
+    #[command(flatten)]
+    rules: RuleArgs,
+
+    /// Print which rules fired for each URL, and where they came from
+    #[arg(long)]
+    explain: bool,
+
+    /// Print version information, add --verbose for the rulesets in use
+    #[arg(short = 'V', long)]
+    version: bool,
+
+    /// With --version, also describe the embedded and active rulesets
+    #[arg(long, requires = "version")]
+    verbose: bool,
+
+    /// One or more URLs to clean
+    #[arg(value_name = "URL", required_unless_present = "version")]
+    urls: Vec<String>,
+}
+
+/// The cleaning options, shared by every command that cleans URLs
+#[derive(Debug, Args)]
+struct CleanArgs {
     /// Do NOT skip localhost URLs
     #[arg(long)]
     no_skip_localhost: bool,
@@ -28,13 +67,153 @@ struct Cli {
     #[arg(long, value_name = "DOMAINS")]
     blacklist: Option<String>,
 
-    /// Comma-separated list of additional blocked params
+    /// Do NOT clean parameters in fragments (`#/page?utm_source=x`, `#xtor=...`)
+    #[arg(long)]
+    no_fragment_cleaning: bool,
+
+    /// Strip text fragments (`#:~:text=...`)
+    #[arg(long)]
+    strip_text_fragments: bool,
+
+    /// Do NOT unwrap AMP caches and proxies (`google.com/amp/s/...`, `*.cdn.ampproject.org`, `*.translate.goog`)
+    #[arg(long)]
+    no_proxy_unwrapping: bool,
+
+    /// Do NOT clean the URLs inside archive and share links (`web.archive.org/web/*/https://...`, `sharer.php?u=...`)
+    #[arg(long)]
+    no_nested_cleaning: bool,
+
+    /// Keep only allowlisted query parameters, removing everything else
+    #[arg(long)]
+    strict: bool,
+
+    /// Comma-separated list of params to keep in strict mode, in Neat URL syntax (`v@*.youtube.com`, `page`)
+    #[arg(long, value_name = "PARAMS", requires = "strict")]
+    allow: Option<String>,
+
+    /// Score the params the rules leave for how much they look like trackers, see --explain
+    #[arg(long)]
+    heuristics: bool,
+
+    /// Remove params whose heuristic score (0 to 1) is at least this
+    #[arg(long, value_name = "SCORE", requires = "heuristics")]
+    heuristic_threshold: Option<f64>,
+
+    /// Reduce links of known sites to their minimal form (Amazon `/dp/<ASIN>`, YouTube `watch?v=`, eBay `/itm/<id>`, ...)
+    #[arg(long)]
+    minimal: bool,
+
+    /// Canonicalize cleaned URLs: lowercase host, no default port or trailing dot, normalized percent-encoding,
+    /// sorted params without empty ones, no duplicate slashes
+    #[arg(long)]
+    canonicalize: bool,
+
+    /// What to do with the trailing slash of the path when canonicalizing
+    #[arg(long, value_enum, default_value = "keep")]
+    trailing_slash: TrailingSlashArg,
+
+    /// Remove `www.` from hosts when canonicalizing
+    #[arg(long)]
+    strip_www: bool,
+
+    /// Comma-separated list of additional blocked params, in Neat URL syntax (`utm_*`, `ref@*.amazon.*`, `$$`, `!param@site`)
     #[arg(long, value_name = "PARAMS")]
     additional_params: Option<String>,
+}
 
-    /// One or more URLs to clean
-    #[arg(value_name = "URL", required = true)]
-    urls: Vec<String>,
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum TrailingSlashArg {
+    Keep,
+    Add,
+    Remove,
+}
+
+/// User rule files, shared by every command that runs the cached or embedded rules
+#[derive(Debug, Args)]
+struct RuleArgs {
+    /// Rule file (ClearURLs JSON or TOML) to merge on top of the embedded rules, can be repeated
+    #[arg(long = "rules", value_name = "FILE")]
+    rule_files: Vec<PathBuf>,
+}
+
+impl CleanArgs {
+    fn options(&self) -> CleaningOptions {
+        CleaningOptions {
+            skip_localhost: !self.no_skip_localhost,
+            apply_referral_marketing: !self.no_referral_marketing,
+            domain_blocking: !self.no_domain_blocking,
+            additional_blocked_params: parse_csv(self.additional_params.as_deref()),
+            blacklisted_domains: parse_csv(self.blacklist.as_deref()),
+            clean_fragments: !self.no_fragment_cleaning,
+            strip_text_fragments: self.strip_text_fragments,
+            unwrap_proxies: !self.no_proxy_unwrapping,
+            clean_nested: !self.no_nested_cleaning,
+            mode: if self.strict {
+                CleaningMode::Strict
+            } else {
+                CleaningMode::Rules
+            },
+            allowed_params: parse_csv(self.allow.as_deref()),
+            heuristics: self.heuristics,
+            heuristic_threshold: self.heuristic_threshold,
+            minimal_form: self.minimal,
+            canonicalize: Canonicalization {
+                trailing_slash: match self.trailing_slash {
+                    TrailingSlashArg::Keep => TrailingSlash::Keep,
+                    TrailingSlashArg::Add => TrailingSlash::Add,
+                    TrailingSlashArg::Remove => TrailingSlash::Remove,
+                },
+                strip_www: self.strip_www,
+                ..if self.canonicalize {
+                    Canonicalization::standard()
+                } else {
+                    Canonicalization::default()
+                }
+            },
+        }
+    }
+}
+
+impl RuleArgs {
+    /// The cached or embedded rules, with any user overlays on top
+    fn rules(&self) -> Result<RuleSet> {
+        let mut rules = RuleSet::active().clone();
+        for path in &self.rule_files {
+            rules.merge_path(path)?;
+        }
+        Ok(rules)
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Inspect and maintain rulesets
+    #[command(subcommand)]
+    Rules(RulesCommand),
+    /// Group URLs that only differ in tracking, redirect wrappers or spelling, printing one per group
+    Dedup(commands::dedup::DedupArgs),
+}
+
+#[derive(Debug, Subcommand)]
+enum RulesCommand {
+    /// Check rulesets for broken, unreachable or overly broad patterns
+    Lint(commands::lint::LintArgs),
+    /// Compare two rulesets, structurally and by what they do to a corpus of URLs
+    Diff(commands::diff::DiffArgs),
+    /// Run test cases against the cached or embedded rules, plus any user rule files
+    Test(commands::test::TestArgs),
+    /// Fetch a newer ClearURLs ruleset into the local cache
+    Update(commands::update::UpdateArgs),
+    /// Go back to the previously cached ruleset
+    Rollback,
+    /// Convert a filter list from another tool into plink providers
+    Import(commands::import::ImportArgs),
+    /// Convert the rules into a format other tools can enforce
+    Export(commands::export::ExportArgs),
+    /// Find the tracking parameters a corpus of URLs still has after cleaning, as candidate providers
+    Mine(commands::mine::MineArgs),
+    /// Write a provider from an example URL, with test cases, interactively or from flags
+    New(commands::new::NewArgs),
 }
 
 fn parse_csv(input: Option<&str>) -> Vec<String> {
@@ -48,6 +227,76 @@ fn parse_csv(input: Option<&str>) -> Vec<String> {
         .unwrap_or_default()
 }
 
+fn print_explanation(input: &str, result: &CleaningResult) {
+    let status = if result.cancel {
+        "cancelled"
+    } else if result.redirect {
+        "redirected"
+    } else if result.changed {
+        "cleaned"
+    } else {
+        "unchanged"
+    };
+
+    eprintln!("{} ({})", input, status);
+    for rule in &result.rule_matches {
+        eprintln!(
+            "  {} {} {} [{}]",
+            rule.provider, rule.kind, rule.pattern, rule.source
+        );
+        if !rule.decoding.is_empty() {
+            eprintln!("    decoded: {}", rule.decoding.join(" -> "));
+        }
+    }
+    for kept in &result.kept_params {
+        eprintln!("  kept {} {} [{}]", kept.name, kept.rule, kept.source);
+    }
+    if let Some(site) = &result.minimal_form {
+        eprintln!("  minimal form {}", site);
+    }
+    for suspicious in &result.suspicious_params {
+        eprintln!(
+            "  {} {} {:.2} ({})",
+            if suspicious.removed {
+                "removed suspicious"
+            } else {
+                "suspicious"
+            },
+            suspicious.name,
+            suspicious.score,
+            suspicious.reasons.join(", ")
+        );
+    }
+}
+
+fn print_ruleset_info(label: &str, info: &RulesetInfo) {
+    println!("{} rules: {}", label, info.source);
+    println!("  providers: {}, rules: {}", info.providers, info.rules);
+    if let Some(sha256) = &info.sha256 {
+        println!("  sha256: {}", sha256);
+    }
+    if let Some(commit) = &info.commit {
+        println!("  commit: {}", commit);
+    }
+    if let Some(timestamp) = info.timestamp {
+        println!("  timestamp: {}", timestamp);
+    }
+    for overlay in &info.overlays {
+        println!("  overlay: {}", overlay);
+    }
+}
+
+fn version(cli: &Cli) -> Result<()> {
+    println!("plink {}", env!("CARGO_PKG_VERSION"));
+
+    if cli.verbose {
+        print_ruleset_info("embedded", RuleSet::embedded().info());
+        print_ruleset_info("active", cli.rules.rules()?.info());
+    }
+
+    Ok(())
+}
+
 fn main() -> Result<()> {
     // let subscriber = FmtSubscriber::builder()
     //     .with_max_level(Level::TRACE)
@@ -59,20 +308,42 @@ fn main() -> Result<()> {
 
     let cli = Cli::parse();
 
-    let options = CleaningOptions {
-        skip_localhost: !cli.no_skip_localhost,
-        apply_referral_marketing: !cli.no_referral_marketing,
-        domain_blocking: !cli.no_domain_blocking,
-        additional_blocked_params: parse_csv(cli.additional_params.as_deref()),
-        blacklisted_domains: parse_csv(cli.blacklist.as_deref()),
-    };
+    match cli.command {
+        Some(Command::Rules(RulesCommand::Lint(args))) => commands::lint::run(args),
+        Some(Command::Rules(RulesCommand::Diff(args))) => commands::diff::run(args),
+        Some(Command::Rules(RulesCommand::Test(args))) => commands::test::run(args),
+        Some(Command::Rules(RulesCommand::Update(args))) => commands::update::run(args),
+        Some(Command::Rules(RulesCommand::Rollback)) => commands::update::rollback(),
+        Some(Command::Rules(RulesCommand::Import(args))) => commands::import::run(args),
+        Some(Command::Rules(RulesCommand::Export(args))) => commands::export::run(args),
+        Some(Command::Rules(RulesCommand::Mine(args))) => commands::mine::run(args),
+        Some(Command::Rules(RulesCommand::New(args))) => commands::new::run(args),
+        Some(Command::Dedup(args)) => commands::dedup::run(args),
+        None if cli.version => version(&cli),
+        None => clean(&cli),
+    }
+}
 
-    // load the embedded JSON config
-    let cleaner = UrlCleaner::new(options)?;
+fn clean(cli: &Cli) -> Result<()> {
+    let rules = cli.rules.rules()?;
+    let cleaner = UrlCleaner::with_rules(cli.clean.options(), &rules)?;
 
-    for url in cli.urls {
-        match cleaner.clean_url(&url) {
+    if cli.explain {
+        for skipped in rules.skipped() {
+            eprintln!(
+                "skipped {} pattern `{}` of provider {} [{}]: {}",
+                skipped.field, skipped.pattern, skipped.provider, skipped.source, skipped.reason
+            );
+        }
+    }
+
+    for url in &cli.urls {
+        match cleaner.clean_url(url) {
             Ok(result) => {
+                if cli.explain {
+                    print_explanation(url, &result);
+                }
+
                 // Print the cleaned URL
                 println!("{}", result.url);
             }
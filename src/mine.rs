@@ -0,0 +1,271 @@
+//! Mining a corpus of URLs for the tracking parameters the rules miss.
+//!
+//! Every URL is cleaned first, then the parameters that survive are aggregated per registrable domain. Trackers
+//! stand out by taking a different value nearly every time (high cardinality and entropy), by changing between
+//! URLs that are otherwise identical, and often by their name or value shape (see `heuristics`).
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::Result;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{CleaningOptions, ProviderSpec, RuleFile, RuleSet, UrlCleaner, heuristics};
+
+/// Second level labels under which country code domains are registered, like `co.uk`
+const SECOND_LEVELS: &[&str] = &[
+    "co", "com", "net", "org", "gov", "edu", "ac", "ne", "or", "go",
+];
+
+/// Signal weights, adding up to 1
+const CARDINALITY_WEIGHT: f64 = 0.35;
+const VARIES_WEIGHT: f64 = 0.25;
+const ENTROPY_WEIGHT: f64 = 0.2;
+const SHAPE_WEIGHT: f64 = 0.2;
+
+/// Bits per character at which a value counts as fully random
+const MAX_ENTROPY: f64 = 4.0;
+
+/// A parameter that survived cleaning and looks like a tracker
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Candidate {
+    /// The registrable domain, like `example.co.uk`
+    pub domain: String,
+    pub param: String,
+    /// From 0 to 1, higher is more likely a tracker
+    pub score: f64,
+    /// How many cleaned URLs on the domain have the parameter
+    pub occurrences: usize,
+    /// The share of the domain's URLs that have the parameter
+    pub frequency: f64,
+    /// How many different values it took
+    pub distinct_values: usize,
+    /// Distinct values per occurrence, 1 when every value is different
+    pub cardinality: f64,
+    /// Mean value entropy, in bits per character
+    pub entropy: f64,
+    /// Whether it took different values on URLs that are otherwise identical
+    pub varies: bool,
+}
+
+/// The ranked candidates, and the same candidates as rule file providers (one per domain)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MineReport {
+    /// URLs that could be cleaned and weren't blocked
+    pub urls: usize,
+    pub candidates: Vec<Candidate>,
+    pub file: RuleFile,
+}
+
+/// What the corpus says about one parameter on one domain
+#[derive(Default)]
+struct ParamStats {
+    occurrences: usize,
+    values: HashSet<String>,
+    entropy: f64,
+    shape: f64,
+    /// The value seen for each version of the URL without the parameter
+    bases: HashMap<String, String>,
+    varies: bool,
+}
+
+/// Clean every URL and rank the parameters that survive. Parameters seen fewer than `min_occurrences` times on
+/// a domain or scoring under `min_score` are left out.
+pub fn mine(
+    rules: &RuleSet,
+    options: &CleaningOptions,
+    urls: &[String],
+    min_occurrences: usize,
+    min_score: f64,
+) -> Result<MineReport> {
+    let cleaner = UrlCleaner::with_rules(options.clone(), rules)?;
+
+    let cleaned: Vec<Url> = urls
+        .par_iter()
+        .filter_map(|url| {
+            let result = cleaner.clean_url(url).ok()?;
+            (!result.cancel)
+                .then(|| Url::parse(&result.url).ok())
+                .flatten()
+        })
+        .collect();
+
+    let mut domain_urls: HashMap<String, usize> = HashMap::new();
+    let mut stats: HashMap<(String, String), ParamStats> = HashMap::new();
+
+    for url in &cleaned {
+        let Some(host) = url.host_str() else {
+            continue;
+        };
+        let domain = registrable_domain(host);
+        *domain_urls.entry(domain.clone()).or_default() += 1;
+
+        let pairs: Vec<(String, String)> = url.query_pairs().into_owned().collect();
+        for (i, (key, value)) in pairs.iter().enumerate() {
+            let param = stats.entry((domain.clone(), key.clone())).or_default();
+            param.occurrences += 1;
+            param.values.insert(value.clone());
+            param.entropy += heuristics::entropy(value);
+            param.shape += heuristics::score(key, value).score;
+
+            let base = base_url(url, &pairs, i);
+            match param.bases.get(&base) {
+                Some(seen) if seen != value => param.varies = true,
+                Some(_) => {}
+                None => {
+                    param.bases.insert(base, value.clone());
+                }
+            }
+        }
+    }
+
+    let mut candidates: Vec<Candidate> = stats
+        .into_iter()
+        .filter(|(_, param)| param.occurrences >= min_occurrences)
+        .map(|((domain, name), param)| {
+            let occurrences = param.occurrences as f64;
+            let cardinality = param.values.len() as f64 / occurrences;
+            let entropy = param.entropy / occurrences;
+            let shape = param.shape / occurrences;
+
+            let score = CARDINALITY_WEIGHT * cardinality
+                + VARIES_WEIGHT * if param.varies { 1.0 } else { 0.0 }
+                + ENTROPY_WEIGHT * (entropy / MAX_ENTROPY).min(1.0)
+                + SHAPE_WEIGHT * shape;
+
+            Candidate {
+                frequency: occurrences / domain_urls[&domain] as f64,
+                domain,
+                param: name,
+                score: round(score),
+                occurrences: param.occurrences,
+                distinct_values: param.values.len(),
+                cardinality: round(cardinality),
+                entropy: round(entropy),
+                varies: param.varies,
+            }
+        })
+        .filter(|candidate| candidate.score >= min_score)
+        .collect();
+
+    candidates.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.occurrences.cmp(&a.occurrences))
+            .then_with(|| (&a.domain, &a.param).cmp(&(&b.domain, &b.param)))
+    });
+
+    Ok(MineReport {
+        urls: cleaned.len(),
+        file: to_file(&candidates),
+        candidates,
+    })
+}
+
+/// One `mined:<domain>` provider per domain, with its parameters from most to least likely
+fn to_file(candidates: &[Candidate]) -> RuleFile {
+    let mut providers: BTreeMap<String, ProviderSpec> = BTreeMap::new();
+
+    for candidate in candidates {
+        let spec = providers
+            .entry(format!("mined:{}", candidate.domain))
+            .or_insert_with(|| {
+                let domains = ProviderSpec {
+                    domains: vec![candidate.domain.clone()],
+                    ..Default::default()
+                };
+                ProviderSpec {
+                    url_pattern: domains.url_pattern(),
+                    ..Default::default()
+                }
+            });
+        spec.rules.push(regex::escape(&candidate.param));
+    }
+
+    RuleFile { providers }
+}
+
+/// The domain a host is registered under: the last two labels, or three under a country code second level
+/// domain like `co.uk`. IP addresses are kept as they are.
+pub fn registrable_domain(host: &str) -> String {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    if host.parse::<std::net::IpAddr>().is_ok() || host.starts_with('[') {
+        return host;
+    }
+
+    let labels: Vec<&str> = host.split('.').collect();
+    let keep = match labels.as_slice() {
+        [.., second, tld] if tld.len() == 2 && SECOND_LEVELS.contains(second) => 3,
+        _ => 2,
+    };
+    labels[labels.len().saturating_sub(keep)..].join(".")
+}
+
+/// The URL without its `skip`th parameter, to find URLs that only differ in that parameter
+fn base_url(url: &Url, pairs: &[(String, String)], skip: usize) -> String {
+    let others: Vec<String> = pairs
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != skip)
+        .map(|(_, (key, value))| format!("{}={}", key, value))
+        .collect();
+    format!(
+        "{}{}?{}",
+        url.host_str().unwrap_or_default(),
+        url.path(),
+        others.join("&")
+    )
+}
+
+fn round(value: f64) -> f64 {
+    (value * 100.0).round() / 100.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RuleSource;
+
+    #[test]
+    fn test_mine_corpus() {
+        let urls: Vec<String> = (0..6)
+            .map(|i| {
+                format!(
+                    "https://www.shop.test/item?id={}&lang=en&visit=x{:08x}&utm_source=a",
+                    i % 2,
+                    i * 2_654_435_761u64
+                )
+            })
+            .collect();
+
+        let rules = RuleSet::from_file(
+            &RuleFile::from_json(
+                r#"{"providers": {"globalRules": {"urlPattern": ".*", "rules": ["utm_source"]}}}"#,
+            )
+            .unwrap(),
+            RuleSource::BuiltIn,
+        )
+        .unwrap();
+
+        let report = mine(&rules, &CleaningOptions::default(), &urls, 2, 0.5).unwrap();
+        assert_eq!(report.urls, 6);
+
+        let params: Vec<&str> = report
+            .candidates
+            .iter()
+            .map(|candidate| candidate.param.as_str())
+            .collect();
+        assert_eq!(params, vec!["visit"]);
+        assert!(report.candidates[0].varies);
+        assert_eq!(report.candidates[0].domain, "shop.test");
+        assert_eq!(
+            report.file.providers["mined:shop.test"].rules,
+            vec!["visit"]
+        );
+
+        assert_eq!(registrable_domain("www.bbc.co.uk"), "bbc.co.uk");
+        assert_eq!(registrable_domain("a.b.example.com"), "example.com");
+        assert_eq!(registrable_domain("10.0.0.1"), "10.0.0.1");
+    }
+}
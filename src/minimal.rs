@@ -0,0 +1,212 @@
+//! Minimal forms: the shortest URL some sites answer with the same page, like `/dp/<ASIN>` on Amazon.
+//!
+//! These go further than ClearURLs by dropping everything that isn't part of the item's identity, including
+//! the product title slugs and share suffixes that no parameter rule can reach.
+
+use url::Url;
+
+use crate::mine::registrable_domain;
+
+/// A site's transform, returning the minimal form of the URLs it knows
+struct Transform {
+    name: &'static str,
+    apply: fn(&Url, &str) -> Option<String>,
+}
+
+const TRANSFORMS: &[Transform] = &[
+    Transform {
+        name: "amazon",
+        apply: amazon,
+    },
+    Transform {
+        name: "youtube",
+        apply: youtube,
+    },
+    Transform {
+        name: "ebay",
+        apply: ebay,
+    },
+    Transform {
+        name: "aliexpress",
+        apply: aliexpress,
+    },
+    Transform {
+        name: "twitter",
+        apply: twitter,
+    },
+];
+
+/// The minimal form of a URL and the transform that produced it, if a transform knows the URL and it isn't
+/// minimal already
+pub(crate) fn minimize(url: &Url) -> Option<(Url, &'static str)> {
+    let host = url.host_str()?;
+    let domain = registrable_domain(host);
+    let site = domain.split('.').next().unwrap_or_default();
+
+    TRANSFORMS.iter().find_map(|transform| {
+        let minimal = (transform.apply)(url, site)?;
+        let minimal = Url::parse(&minimal).ok()?;
+        (minimal != *url).then_some((minimal, transform.name))
+    })
+}
+
+/// `/dp/<ASIN>`, from product pages with or without a title slug and from `/gp/product/<ASIN>`
+fn amazon(url: &Url, site: &str) -> Option<String> {
+    if site != "amazon" {
+        return None;
+    }
+
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    let asin = segments.windows(2).find_map(|pair| match pair {
+        ["dp", asin] | ["product", asin] | ["d", asin] if is_asin(asin) => Some(*asin),
+        _ => None,
+    })?;
+
+    Some(format!("https://{}/dp/{}", url.host_str()?, asin))
+}
+
+fn is_asin(text: &str) -> bool {
+    text.len() == 10
+        && text
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+/// `www.youtube.com/watch?v=<id>`, from `youtu.be`, mobile and Shorts links, keeping the timestamp
+fn youtube(url: &Url, site: &str) -> Option<String> {
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    let id = match (site, segments.as_slice()) {
+        ("youtu", [id]) => id.to_string(),
+        ("youtube", ["watch"]) => url
+            .query_pairs()
+            .find_map(|(key, value)| (key == "v").then(|| value.into_owned()))?,
+        ("youtube", ["shorts" | "live", id]) => id.to_string(),
+        _ => return None,
+    };
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+
+    let time = url
+        .query_pairs()
+        .find_map(|(key, value)| (key == "t").then(|| value.into_owned()));
+    Some(match time {
+        Some(time) => format!("https://www.youtube.com/watch?v={}&t={}", id, time),
+        None => format!("https://www.youtube.com/watch?v={}", id),
+    })
+}
+
+/// `/itm/<id>`, without the title slug
+fn ebay(url: &Url, site: &str) -> Option<String> {
+    if site != "ebay" {
+        return None;
+    }
+
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    let id = match segments.as_slice() {
+        ["itm", id] | ["itm", _, id] if is_number(id) => *id,
+        _ => return None,
+    };
+
+    Some(format!("https://{}/itm/{}", url.host_str()?, id))
+}
+
+/// `/item/<id>.html`, from desktop and mobile item pages
+fn aliexpress(url: &Url, site: &str) -> Option<String> {
+    if site != "aliexpress" {
+        return None;
+    }
+
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    let id = match segments.as_slice() {
+        ["item", page] | ["i", page] | ["item", _, page] => page.strip_suffix(".html")?,
+        _ => return None,
+    };
+    if !is_number(id) {
+        return None;
+    }
+
+    Some(format!("https://{}/item/{}.html", url.host_str()?, id))
+}
+
+/// `/<user>/status/<id>`, without `/photo/1` style suffixes and the `s=`/`t=` share parameters
+fn twitter(url: &Url, site: &str) -> Option<String> {
+    if site != "twitter" && site != "x" {
+        return None;
+    }
+
+    let segments: Vec<&str> = url.path_segments()?.collect();
+    let (user, id) = match segments.as_slice() {
+        [user, "status" | "statuses", id, ..] if is_number(id) => (*user, *id),
+        _ => return None,
+    };
+
+    Some(format!(
+        "https://{}/{}/status/{}",
+        url.host_str()?,
+        user,
+        id
+    ))
+}
+
+fn is_number(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minimal(url: &str) -> Option<(String, &'static str)> {
+        minimize(&Url::parse(url).unwrap()).map(|(url, name)| (url.to_string(), name))
+    }
+
+    #[test]
+    fn test_minimal_forms() {
+        let cases = [
+            (
+                "https://www.amazon.co.uk/Some-Product-Title/dp/B08N5WRWNW/ref=sr_1_1?keywords=x",
+                "https://www.amazon.co.uk/dp/B08N5WRWNW",
+                "amazon",
+            ),
+            (
+                "https://youtu.be/dQw4w9WgXcQ?si=abc&t=42",
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42",
+                "youtube",
+            ),
+            (
+                "https://m.youtube.com/watch?v=dQw4w9WgXcQ&list=PL1&index=3",
+                "https://www.youtube.com/watch?v=dQw4w9WgXcQ",
+                "youtube",
+            ),
+            (
+                "https://www.ebay.com/itm/Vintage-Lamp/123456789012?hash=item1",
+                "https://www.ebay.com/itm/123456789012",
+                "ebay",
+            ),
+            (
+                "https://m.aliexpress.com/item/1005001234567890.html?spm=a2g0o",
+                "https://m.aliexpress.com/item/1005001234567890.html",
+                "aliexpress",
+            ),
+            (
+                "https://x.com/rustlang/status/1234567890/photo/1?s=20&t=abc",
+                "https://x.com/rustlang/status/1234567890",
+                "twitter",
+            ),
+        ];
+        for (url, expected, name) in cases {
+            assert_eq!(minimal(url), Some((expected.to_string(), name)), "{}", url);
+        }
+
+        // Already minimal, or not an item page
+        assert_eq!(minimal("https://www.amazon.com/dp/B08N5WRWNW"), None);
+        assert_eq!(minimal("https://www.amazon.com/gp/cart/view.html"), None);
+        assert_eq!(minimal("https://x.com/rustlang"), None);
+        assert_eq!(minimal("https://example.com/dp/B08N5WRWNW"), None);
+    }
+}
@@ -0,0 +1,231 @@
+//! Neat URL style rules, as accepted by `CleaningOptions::additional_blocked_params`.
+//!
+//! - `param` removes a parameter everywhere, `utm_*` every parameter starting with `utm_`
+//! - `param@example.com` only on that host, `param@*.example.com` also on its subdomains, and
+//!   `param@example.*` on any top level domain
+//! - `$/ref@amazon.*` cuts the URL from `/ref` onwards
+//! - `$$` drops the fragment, `$$text` only a fragment starting with `text`
+//! - `!param@example.com` keeps a parameter that another rule (or a provider) would remove
+
+use anyhow::{Result, bail};
+use url::{Position, Url};
+
+/// A parsed Neat URL rule
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct NeatRule {
+    /// The rule as written
+    pub(crate) source: String,
+    action: Action,
+    domain: Option<DomainMatch>,
+    /// `!` rules keep what they match instead of removing it
+    pub(crate) exclude: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Action {
+    Param(ParamMatch),
+    /// Cut the URL from this text onwards
+    Cut(String),
+    /// Drop the fragment, if it starts with the text
+    Fragment(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ParamMatch {
+    Exact(String),
+    Prefix(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct DomainMatch {
+    name: String,
+    subdomains: bool,
+    any_tld: bool,
+}
+
+impl NeatRule {
+    pub(crate) fn parse(rule: &str) -> Result<Self> {
+        let source = rule.trim().to_string();
+        let (exclude, rule) = match source.strip_prefix('!') {
+            Some(rule) => (true, rule),
+            None => (false, source.as_str()),
+        };
+
+        let (body, domain) = match rule.rsplit_once('@') {
+            Some((body, domain)) => (body, Some(DomainMatch::parse(domain)?)),
+            None => (rule, None),
+        };
+
+        let action = if let Some(text) = body.strip_prefix("$$") {
+            Action::Fragment(text.to_string())
+        } else if let Some(text) = body.strip_prefix('$') {
+            if text.is_empty() {
+                bail!("Neat URL rule '{}' has nothing to cut from", source);
+            }
+            Action::Cut(text.to_string())
+        } else if let Some(prefix) = body.strip_suffix('*') {
+            Action::Param(ParamMatch::Prefix(prefix.to_string()))
+        } else if body.is_empty() {
+            bail!("Neat URL rule '{}' has no parameter", source);
+        } else {
+            Action::Param(ParamMatch::Exact(body.to_string()))
+        };
+
+        if exclude && !matches!(action, Action::Param(_)) {
+            bail!("Neat URL rule '{}' can only exclude parameters", source);
+        }
+
+        Ok(Self {
+            source,
+            action,
+            domain,
+            exclude,
+        })
+    }
+
+    /// Whether the rule applies to a URL on this host
+    pub(crate) fn applies_to(&self, host: &str) -> bool {
+        self.domain
+            .as_ref()
+            .is_none_or(|domain| domain.matches(host))
+    }
+
+    /// Whether this is a parameter rule matching `key`
+    pub(crate) fn matches_param(&self, key: &str) -> bool {
+        match &self.action {
+            Action::Param(ParamMatch::Exact(name)) => key == name,
+            Action::Param(ParamMatch::Prefix(prefix)) => key.starts_with(prefix.as_str()),
+            _ => false,
+        }
+    }
+
+    /// Whether the rule is about parameters, rather than cutting the URL
+    pub(crate) fn is_param(&self) -> bool {
+        matches!(self.action, Action::Param(_))
+    }
+
+    /// The parameter name, for a rule that removes exactly one parameter on every site
+    pub(crate) fn global_param(&self) -> Option<&str> {
+        match &self.action {
+            Action::Param(ParamMatch::Exact(name)) if self.domain.is_none() && !self.exclude => {
+                Some(name)
+            }
+            _ => None,
+        }
+    }
+
+    /// Apply a `$` or `$$` rule, returning whether the URL changed
+    pub(crate) fn cut(&self, url: &mut Url) -> Result<bool> {
+        match &self.action {
+            Action::Fragment(text) => {
+                if url
+                    .fragment()
+                    .is_some_and(|fragment| fragment.starts_with(text.as_str()))
+                {
+                    url.set_fragment(None);
+                    return Ok(true);
+                }
+                Ok(false)
+            }
+            Action::Cut(text) => {
+                // Only look past the host, so `$.com` can't cut into the domain
+                let start = url[..Position::BeforePath].len();
+                let Some(position) = url.as_str()[start..].find(text.as_str()) else {
+                    return Ok(false);
+                };
+
+                let cut = &url.as_str()[..start + position];
+                *url = Url::parse(cut)?;
+                Ok(true)
+            }
+            Action::Param(_) => Ok(false),
+        }
+    }
+}
+
+impl DomainMatch {
+    fn parse(domain: &str) -> Result<Self> {
+        let (subdomains, name) = match domain.strip_prefix("*.") {
+            Some(name) => (true, name),
+            None => (false, domain),
+        };
+        let (any_tld, name) = match name.strip_suffix(".*") {
+            Some(name) => (true, name),
+            None => (false, name),
+        };
+
+        if name.is_empty() || name.contains(['*', '/', '@']) {
+            bail!("Invalid Neat URL domain '{}'", domain);
+        }
+
+        Ok(Self {
+            name: name.to_ascii_lowercase(),
+            subdomains,
+            any_tld,
+        })
+    }
+
+    fn matches(&self, host: &str) -> bool {
+        let host = host.to_ascii_lowercase();
+
+        // With `.*`, drop one or two short top level labels (`.com`, `.co.uk`) before comparing
+        let candidates: Vec<&str> = if self.any_tld {
+            let mut candidates = Vec::new();
+            let mut rest = host.as_str();
+            for _ in 0..2 {
+                let Some((name, tld)) = rest.rsplit_once('.') else {
+                    break;
+                };
+                if tld.len() < 2 || !tld.chars().all(|c| c.is_ascii_alphabetic()) {
+                    break;
+                }
+                candidates.push(name);
+                rest = name;
+            }
+            candidates
+        } else {
+            vec![host.as_str()]
+        };
+
+        candidates.into_iter().any(|candidate| {
+            candidate == self.name
+                || (self.subdomains
+                    && candidate
+                        .strip_suffix(self.name.as_str())
+                        .is_some_and(|prefix| prefix.ends_with('.')))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_neat_url_rules() {
+        let rule = NeatRule::parse("ref@*.amazon.*").unwrap();
+        assert!(rule.applies_to("www.amazon.co.uk"));
+        assert!(rule.applies_to("amazon.de"));
+        assert!(!rule.applies_to("notamazon.com"));
+        assert!(rule.matches_param("ref"));
+
+        let rule = NeatRule::parse("utm_*").unwrap();
+        assert!(rule.applies_to("example.com"));
+        assert!(rule.matches_param("utm_source"));
+        assert!(!rule.matches_param("xutm_source"));
+
+        let rule = NeatRule::parse("$/ref@amazon.*").unwrap();
+        let mut url = Url::parse("https://amazon.com/dp/B0/ref=sr_1?keywords=x").unwrap();
+        assert!(rule.cut(&mut url).unwrap());
+        assert_eq!(url.as_str(), "https://amazon.com/dp/B0");
+
+        let rule = NeatRule::parse("$$").unwrap();
+        let mut url = Url::parse("https://example.com/page#tracking").unwrap();
+        assert!(rule.cut(&mut url).unwrap());
+        assert_eq!(url.as_str(), "https://example.com/page");
+
+        assert!(NeatRule::parse("!fbclid@example.com").unwrap().exclude);
+        assert!(NeatRule::parse("!$$@example.com").is_err());
+        assert!(NeatRule::parse("ref@").is_err());
+    }
+}
@@ -0,0 +1,276 @@
+//! URLs embedded in archive and share links, which are cleaned in place rather than unwrapped.
+//!
+//! Archives keep the page's URL as is at the end of their path (`web.archive.org/web/2023/https://...`), share
+//! dialogs percent-encode it into a parameter (`facebook.com/sharer/sharer.php?u=...`). Either way the cleaned
+//! URL goes back where it came from, encoded the way it was.
+
+use url::{Position, Url};
+
+use crate::mine::registrable_domain;
+
+/// Share dialogs by site, with their paths and the parameter holding the shared URL
+const SHARE_DIALOGS: &[(&str, &[&str], &str)] = &[
+    ("facebook", &["/sharer/sharer.php", "/sharer.php"], "u"),
+    (
+        "twitter",
+        &["/intent/tweet", "/intent/post", "/share"],
+        "url",
+    ),
+    ("x", &["/intent/tweet", "/intent/post", "/share"], "url"),
+    (
+        "linkedin",
+        &["/sharing/share-offsite", "/shareArticle"],
+        "url",
+    ),
+    ("reddit", &["/submit"], "url"),
+];
+
+/// archive.today and its mirrors
+const ARCHIVE_TODAY: &[&str] = &[
+    "archive.ph",
+    "archive.today",
+    "archive.is",
+    "archive.li",
+    "archive.vn",
+    "archive.md",
+    "archive.fo",
+];
+
+/// A URL inside another one
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Embedded {
+    /// The wrapper kind, `archive` or `share`
+    pub(crate) wrapper: &'static str,
+    /// The embedded URL, decoded
+    pub(crate) inner: String,
+    place: Place,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Place {
+    /// Everything from this byte of the wrapper on
+    Path(usize),
+    /// The value of a query parameter
+    Param {
+        name: &'static str,
+        /// The ASCII characters that were percent-encoded, and the ones left as they were
+        escaped: Vec<u8>,
+        literal: Vec<u8>,
+        /// Whether the escapes used lowercase hex digits
+        lowercase: bool,
+    },
+}
+
+/// The URL embedded in an archive or share link, if `url` is one
+pub(crate) fn find(url: &Url) -> Option<Embedded> {
+    let host = url.host_str()?;
+    let domain = registrable_domain(host);
+    let site = domain.split('.').next().unwrap_or_default();
+
+    let archived = (host == "web.archive.org" && url.path().starts_with("/web/"))
+        || ARCHIVE_TODAY.contains(&domain.as_str());
+    if archived {
+        // Past the host, so `https://web.archive.org` itself doesn't count
+        let serialized = url.as_str();
+        let after_host = url[..Position::BeforePath].len();
+        let start = ["/https://", "/http://"]
+            .iter()
+            .filter_map(|scheme| serialized[after_host..].find(scheme))
+            .min()?
+            + after_host
+            + 1;
+
+        let inner = &serialized[start..];
+        Url::parse(inner).ok()?;
+        return Some(Embedded {
+            wrapper: "archive",
+            inner: inner.to_string(),
+            place: Place::Path(start),
+        });
+    }
+
+    let (_, _, name) = SHARE_DIALOGS.iter().find(|(dialog, paths, _)| {
+        *dialog == site && paths.iter().any(|path| url.path() == *path)
+    })?;
+    let raw = url
+        .query()?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix(name)?.strip_prefix('='))?;
+    let inner = url
+        .query_pairs()
+        .find_map(|(key, value)| (key == *name).then(|| value.into_owned()))?;
+    if !inner.starts_with("http") {
+        return None;
+    }
+    Url::parse(&inner).ok()?;
+
+    let (escaped, literal) = escapes(raw);
+    Some(Embedded {
+        wrapper: "share",
+        place: Place::Param {
+            name,
+            escaped,
+            literal,
+            lowercase: has_lowercase_escape(raw),
+        },
+        inner,
+    })
+}
+
+impl Embedded {
+    /// The wrapper with `cleaned` in place of the embedded URL, encoded the same way
+    pub(crate) fn embed(&self, url: &Url, cleaned: &str) -> Option<Url> {
+        match &self.place {
+            Place::Path(start) => {
+                Url::parse(&format!("{}{}", &url.as_str()[..*start], cleaned)).ok()
+            }
+            Place::Param {
+                name,
+                escaped,
+                literal,
+                lowercase,
+            } => {
+                let value = encode_like(cleaned, escaped, literal, *lowercase);
+
+                let query: Vec<String> = url
+                    .query()?
+                    .split('&')
+                    .map(|pair| match pair.strip_prefix(name) {
+                        Some(rest) if rest.starts_with('=') => format!("{}={}", name, value),
+                        _ => pair.to_string(),
+                    })
+                    .collect();
+
+                let mut wrapper = url.clone();
+                wrapper.set_query(Some(&query.join("&")));
+                Some(wrapper)
+            }
+        }
+    }
+}
+
+/// The ASCII characters `raw` percent-encodes, and the ones it leaves as they are
+fn escapes(raw: &str) -> (Vec<u8>, Vec<u8>) {
+    let (mut escaped, mut literal) = (Vec::new(), Vec::new());
+    let bytes = raw.as_bytes();
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = raw.get(i + 1..i + 3).filter(|_| bytes[i] == b'%');
+        match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+            Some(byte) => {
+                if byte.is_ascii() && !escaped.contains(&byte) {
+                    escaped.push(byte);
+                }
+                i += 3;
+            }
+            None => {
+                if bytes[i].is_ascii() && !literal.contains(&bytes[i]) {
+                    literal.push(bytes[i]);
+                }
+                i += 1;
+            }
+        }
+    }
+    (escaped, literal)
+}
+
+/// Percent-encode `cleaned` the way the original value was: the characters it escaped are escaped again and the
+/// ones it left alone stay as they are. Characters it had neither way are escaped if it escaped anything at all.
+fn encode_like(cleaned: &str, escaped: &[u8], literal: &[u8], lowercase: bool) -> String {
+    let mut value = String::with_capacity(cleaned.len());
+    let mut buffer = [0; 4];
+
+    for c in cleaned.chars() {
+        // A `+` in the original value decoded to a space
+        if c == ' ' && literal.contains(&b'+') && !escaped.contains(&b' ') {
+            value.push('+');
+            continue;
+        }
+
+        let escape = match u8::try_from(c).ok().filter(u8::is_ascii) {
+            // Either would end the parameter
+            Some(b'&' | b'#') => true,
+            Some(byte) if escaped.contains(&byte) => true,
+            Some(byte) if literal.contains(&byte) => false,
+            Some(byte) if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) => false,
+            _ => !escaped.is_empty(),
+        };
+        if !escape {
+            value.push(c);
+            continue;
+        }
+
+        for byte in c.encode_utf8(&mut buffer).bytes() {
+            if lowercase {
+                value.push_str(&format!("%{:02x}", byte));
+            } else {
+                value.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+    value
+}
+
+fn has_lowercase_escape(text: &str) -> bool {
+    text.split('%')
+        .skip(1)
+        .any(|escape| escape.chars().take(2).any(|c| c.is_ascii_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_and_embed() {
+        let url =
+            Url::parse("https://web.archive.org/web/2023*/https://site.test/a?id=1&utm_source=x")
+                .unwrap();
+        let embedded = find(&url).unwrap();
+        assert_eq!(embedded.wrapper, "archive");
+        assert_eq!(embedded.inner, "https://site.test/a?id=1&utm_source=x");
+        assert_eq!(
+            embedded
+                .embed(&url, "https://site.test/a?id=1")
+                .unwrap()
+                .as_str(),
+            "https://web.archive.org/web/2023*/https://site.test/a?id=1"
+        );
+
+        let url = Url::parse(
+            "https://www.facebook.com/sharer/sharer.php?u=https%3a%2f%2fsite.test%2f%3fid%3d1%26fbclid%3dx&hl=en",
+        )
+        .unwrap();
+        let embedded = find(&url).unwrap();
+        assert_eq!(embedded.inner, "https://site.test/?id=1&fbclid=x");
+        assert_eq!(
+            embedded
+                .embed(&url, "https://site.test/?id=1")
+                .unwrap()
+                .as_str(),
+            "https://www.facebook.com/sharer/sharer.php?u=https%3a%2f%2fsite.test%2f%3fid%3d1&hl=en"
+        );
+
+        // Only what was escaped before is escaped again
+        let url = Url::parse(
+            "https://twitter.com/intent/tweet?url=https://site.test/a%3Fid%3D1%26utm_source%3Dx&text=hi",
+        )
+        .unwrap();
+        let embedded = find(&url).unwrap();
+        assert_eq!(embedded.inner, "https://site.test/a?id=1&utm_source=x");
+        assert_eq!(
+            embedded
+                .embed(&url, "https://site.test/a?id=1")
+                .unwrap()
+                .as_str(),
+            "https://twitter.com/intent/tweet?url=https://site.test/a%3Fid%3D1&text=hi"
+        );
+
+        assert_eq!(find(&Url::parse("https://web.archive.org/").unwrap()), None);
+        assert_eq!(
+            find(&Url::parse("https://twitter.com/intent/tweet?text=hi").unwrap()),
+            None
+        );
+    }
+}
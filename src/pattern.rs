@@ -0,0 +1,117 @@
+use std::borrow::Cow;
+
+use anyhow::{Result, anyhow};
+use log::debug;
+use regex::Regex;
+
+use crate::js_regex::{self, Usage};
+
+/// A compiled ClearURLs pattern.
+///
+/// Patterns are translated from JavaScript syntax first, and run on Rust's `regex` whenever possible.
+/// With the `backtracking` feature, the few that need lookarounds or backreferences fall back to `fancy-regex`.
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    /// The pattern as written in the rule file
+    source: String,
+    engine: Engine,
+}
+
+#[derive(Debug, Clone)]
+enum Engine {
+    Native(Regex),
+    #[cfg(feature = "backtracking")]
+    Backtracking(fancy_regex::Regex),
+}
+
+impl Pattern {
+    /// Translate and compile a pattern, failing if neither engine can run it
+    pub fn new(pattern: &str, usage: Usage) -> Result<Self> {
+        let translated = js_regex::translate(pattern, usage);
+
+        if !translated.rewrites.is_empty() {
+            debug!(
+                "Rewrote {} in pattern '{}'",
+                translated.rewrites.join(", "),
+                pattern
+            );
+        }
+
+        let native = match translated.unsupported {
+            Some(reason) => Err(anyhow!("{} is not supported by the regex crate", reason)),
+            None => Regex::new(&translated.pattern).map_err(|e| anyhow!(e)),
+        };
+
+        let engine = match native {
+            Ok(regex) => Engine::Native(regex),
+            #[cfg(feature = "backtracking")]
+            Err(_) => {
+                let regex = fancy_regex::Regex::new(&translated.pattern)
+                    .map_err(|e| anyhow!("Invalid pattern '{}': {}", pattern, e))?;
+                debug!("Using the backtracking engine for pattern '{}'", pattern);
+                Engine::Backtracking(regex)
+            }
+            #[cfg(not(feature = "backtracking"))]
+            Err(e) => return Err(anyhow!("Invalid pattern '{}': {}", pattern, e)),
+        };
+
+        Ok(Self {
+            source: pattern.to_string(),
+            engine,
+        })
+    }
+
+    /// The pattern as written in the rule file
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Whether the pattern needed the backtracking engine
+    pub fn is_backtracking(&self) -> bool {
+        !matches!(self.engine, Engine::Native(_))
+    }
+
+    pub fn is_match(&self, haystack: &str) -> bool {
+        match &self.engine {
+            Engine::Native(regex) => regex.is_match(haystack),
+            #[cfg(feature = "backtracking")]
+            Engine::Backtracking(regex) => regex.is_match(haystack).unwrap_or_else(|e| {
+                log::warn!("Pattern '{}' gave up on {}: {}", self.source, haystack, e);
+                false
+            }),
+        }
+    }
+
+    /// The text of a capture group in the first match
+    pub fn capture<'h>(&self, haystack: &'h str, group: usize) -> Option<&'h str> {
+        match &self.engine {
+            Engine::Native(regex) => regex
+                .captures(haystack)
+                .and_then(|captures| captures.get(group))
+                .map(|m| m.as_str()),
+            #[cfg(feature = "backtracking")]
+            Engine::Backtracking(regex) => regex
+                .captures(haystack)
+                .unwrap_or_else(|e| {
+                    log::warn!("Pattern '{}' gave up on {}: {}", self.source, haystack, e);
+                    None
+                })
+                .and_then(|captures| captures.get(group))
+                .map(|m| m.as_str()),
+        }
+    }
+
+    /// Replace every match with `replacement`
+    pub fn replace_all<'h>(&self, haystack: &'h str, replacement: &str) -> Cow<'h, str> {
+        match &self.engine {
+            Engine::Native(regex) => regex.replace_all(haystack, replacement),
+            #[cfg(feature = "backtracking")]
+            Engine::Backtracking(regex) => regex
+                .try_replacen(haystack, 0, replacement)
+                .unwrap_or_else(|e| {
+                    log::warn!("Pattern '{}' gave up on {}: {}", self.source, haystack, e);
+                    Cow::Borrowed(haystack)
+                }),
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Unwrapping of AMP caches and proxies, which serve a page under their own URL.
+//!
+//! ClearURLs redirections can only capture a single percent-encoded target, while these wrappers spread the
+//! origin over the path (`/amp/s/example.com/page`) or even the host (`example-com.translate.goog`), so they're
+//! rebuilt here before the providers run.
+//!
+//! Bing's AMP cache (`*.bing-amp.com`) is the only Bing shape unwrapped. Bing doesn't serve turbo pages under a
+//! URL that carries the origin the way Yandex does, so those are out of scope.
+
+use url::Url;
+
+use crate::mine::registrable_domain;
+
+/// How many wrappers deep an URL is unwrapped, like a Google AMP link to a `.amp` page
+const MAX_DEPTH: usize = 5;
+
+/// A wrapper shape, returning the URL it wraps
+struct Proxy {
+    name: &'static str,
+    apply: fn(&Url, &str) -> Option<String>,
+    /// Only applied right after one of these wrappers, or anywhere if empty
+    after: &'static [&'static str],
+}
+
+const PROXIES: &[Proxy] = &[
+    Proxy {
+        name: "google_amp",
+        apply: google_amp,
+        after: &[],
+    },
+    Proxy {
+        name: "amp_cache",
+        apply: amp_cache,
+        after: &[],
+    },
+    Proxy {
+        name: "yandex_turbo",
+        apply: yandex_turbo,
+        after: &[],
+    },
+    Proxy {
+        name: "google_translate",
+        apply: google_translate,
+        after: &[],
+    },
+    Proxy {
+        name: "amp_path",
+        apply: amp_path,
+        // Plenty of files end in `.amp` too, so only the pages AMP caches serve
+        after: &["google_amp", "amp_cache"],
+    },
+];
+
+/// The origin URL behind every wrapper around `url`, along with the wrappers from the outside in
+pub(crate) fn unwrap(url: &Url) -> Option<(Url, Vec<&'static str>)> {
+    let mut current = url.clone();
+    let mut unwrapped = Vec::new();
+
+    for _ in 0..MAX_DEPTH {
+        let Some(host) = current.host_str() else {
+            break;
+        };
+        let domain = registrable_domain(host);
+
+        let last = unwrapped.last();
+        let Some((origin, name)) = PROXIES.iter().find_map(|proxy| {
+            if !proxy.after.is_empty() && !last.is_some_and(|name| proxy.after.contains(name)) {
+                return None;
+            }
+            let origin = (proxy.apply)(&current, &domain)?;
+            Some((Url::parse(&origin).ok()?, proxy.name))
+        }) else {
+            break;
+        };
+        if origin == current {
+            break;
+        }
+
+        current = origin;
+        unwrapped.push(name);
+    }
+
+    (!unwrapped.is_empty()).then_some((current, unwrapped))
+}
+
+/// `google.com/amp/s/example.com/page`, and `/amp/example.com/page` for plain http
+fn google_amp(url: &Url, domain: &str) -> Option<String> {
+    if !domain.starts_with("google.") {
+        return None;
+    }
+    let (secure, rest) = scheme_prefixed(url.path().strip_prefix("/amp/")?);
+    origin(url, secure, rest)
+}
+
+/// `example-com.cdn.ampproject.org/c/s/example.com/page` and the Bing AMP cache (`*.bing-amp.com`), also for
+/// `/v/` viewer and `/i/` image URLs
+fn amp_cache(url: &Url, domain: &str) -> Option<String> {
+    if domain != "ampproject.org" && domain != "bing-amp.com" {
+        return None;
+    }
+    let path = url.path();
+    let path = ["/c/", "/v/", "/i/"]
+        .iter()
+        .find_map(|prefix| path.strip_prefix(prefix))?;
+    let (secure, rest) = scheme_prefixed(path);
+    origin(url, secure, rest)
+}
+
+/// `yandex.ru/turbo/example.com/s/page`, `yandex.ru/turbo?text=<url>` and
+/// `example-com.turbopages.org/example.com/s/page`
+fn yandex_turbo(url: &Url, domain: &str) -> Option<String> {
+    let path = if domain == "turbopages.org" {
+        url.path().strip_prefix('/')?
+    } else if domain.starts_with("yandex.") {
+        let path = url.path();
+        if path == "/turbo" || path == "/turbo/" {
+            return url.query_pairs().find_map(|(key, value)| {
+                (key == "text" && value.starts_with("http")).then(|| value.into_owned())
+            });
+        }
+        path.strip_prefix("/turbo/")?
+    } else {
+        return None;
+    };
+
+    // The origin host, then `/s/` and the origin path
+    let (host, page) = path
+        .split_once("/s/")
+        .or_else(|| path.strip_suffix("/s").map(|host| (host, "")))?;
+    let host = host.rsplit('/').next()?;
+    origin(url, true, &format!("{}/{}", host, page))
+}
+
+/// `example-com.translate.goog/page?_x_tr_sl=auto&_x_tr_tl=en`, whose host encodes `.` as `-` and `-` as `--`
+fn google_translate(url: &Url, domain: &str) -> Option<String> {
+    if domain != "translate.goog" {
+        return None;
+    }
+    let encoded = url.host_str()?.strip_suffix(".translate.goog")?;
+    let host = encoded
+        .replace("--", "\u{0}")
+        .replace('-', ".")
+        .replace('\u{0}', "-");
+
+    let mut origin = url.clone();
+    origin.set_host(Some(&host)).ok()?;
+    let pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(key, _)| !key.starts_with("_x_tr_"))
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if pairs.is_empty() {
+        origin.set_query(None);
+    } else {
+        origin.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    Some(origin.to_string())
+}
+
+/// `example.com/story.amp` and `example.com/story.amp.html`, the AMP versions of pages published next to them,
+/// as served from an AMP cache
+fn amp_path(url: &Url, _domain: &str) -> Option<String> {
+    let path = url.path();
+    let canonical = match path.strip_suffix(".amp") {
+        Some(page) => page.to_string(),
+        None => format!("{}.html", path.strip_suffix(".amp.html")?),
+    };
+    if canonical.is_empty() || canonical.ends_with('/') {
+        return None;
+    }
+
+    let mut origin = url.clone();
+    origin.set_path(&canonical);
+    Some(origin.to_string())
+}
+
+/// Whether the wrapped URL was https (`s/` in front of it), and the URL without the marker
+fn scheme_prefixed(path: &str) -> (bool, &str) {
+    match path.strip_prefix("s/") {
+        Some(rest) => (true, rest),
+        None => (false, path),
+    }
+}
+
+/// The wrapped URL from its host and path, keeping the wrapper's query and fragment
+fn origin(url: &Url, secure: bool, host_and_path: &str) -> Option<String> {
+    let host = host_and_path.split('/').next()?;
+    if !host.contains('.') {
+        return None;
+    }
+
+    let mut origin = format!(
+        "{}://{}",
+        if secure { "https" } else { "http" },
+        host_and_path
+    );
+    if let Some(query) = url.query() {
+        origin.push('?');
+        origin.push_str(query);
+    }
+    if let Some(fragment) = url.fragment() {
+        origin.push('#');
+        origin.push_str(fragment);
+    }
+    Some(origin)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unwrapped(url: &str) -> Option<(String, Vec<&'static str>)> {
+        unwrap(&Url::parse(url).unwrap()).map(|(url, names)| (url.to_string(), names))
+    }
+
+    #[test]
+    fn test_unwrap_proxies() {
+        let cases = [
+            (
+                "https://www.google.com/amp/s/news.example.com/story.amp?utm_source=x",
+                "https://news.example.com/story?utm_source=x",
+                vec!["google_amp", "amp_path"],
+            ),
+            (
+                "https://news-example-com.cdn.ampproject.org/c/s/news.example.com/a/b",
+                "https://news.example.com/a/b",
+                vec!["amp_cache"],
+            ),
+            (
+                "https://www-example-com.bing-amp.com/c/www.example.com/page",
+                "http://www.example.com/page",
+                vec!["amp_cache"],
+            ),
+            (
+                "https://yandex.ru/turbo/example.com/s/news/123?utm_source=yxnews",
+                "https://example.com/news/123?utm_source=yxnews",
+                vec!["yandex_turbo"],
+            ),
+            (
+                "https://yandex.ru/turbo?text=https%3A%2F%2Fexample.com%2Fnews",
+                "https://example.com/news",
+                vec!["yandex_turbo"],
+            ),
+            (
+                "https://my--blog-example-com.translate.goog/post?id=1&_x_tr_sl=auto&_x_tr_tl=en",
+                "https://my-blog.example.com/post?id=1",
+                vec!["google_translate"],
+            ),
+        ];
+        for (url, expected, names) in cases {
+            assert_eq!(
+                unwrapped(url),
+                Some((expected.to_string(), names)),
+                "{}",
+                url
+            );
+        }
+
+        assert_eq!(unwrapped("https://www.google.com/search?q=amp"), None);
+        assert_eq!(unwrapped("https://example.com/amp/"), None);
+        assert_eq!(unwrapped("https://cdn.example.org/audio/track.amp"), None);
+    }
+}
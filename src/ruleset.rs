@@ -0,0 +1,720 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    path::Path,
+    slice,
+    sync::{Arc, LazyLock},
+};
+
+use anyhow::{Context, Result};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tsify::Tsify;
+use url::Url;
+
+use crate::{
+    js_regex::Usage,
+    pattern::Pattern,
+    rules::{BUILD_INFO, PROVIDERS},
+};
+
+/// The embedded ClearURLs data, converted once into the runtime representation
+static EMBEDDED: LazyLock<RuleSet> = LazyLock::new(|| {
+    let providers = PROVIDERS
+        .iter()
+        .map(|provider| {
+            let patterns =
+                |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+
+            let spec = ProviderSpec {
+                url_pattern: Some(provider.url_pattern.to_string()),
+                rules: patterns(provider.rules),
+                raw_rules: patterns(provider.raw_rules),
+                exceptions: patterns(provider.exceptions),
+                redirections: patterns(provider.redirections),
+                referral_marketing: patterns(provider.referral_marketing),
+                complete_provider: Some(provider.complete_provider),
+                force_redirection: Some(provider.force_redirection),
+                ..Default::default()
+            };
+            (provider.name.to_string(), spec)
+        })
+        .collect();
+
+    let mut set = RuleSet::default();
+    set.merge(&RuleFile { providers }, RuleSource::BuiltIn)
+        .expect("Every embedded provider has a urlPattern");
+
+    // Patterns that were already dropped by the build script
+    set.skipped
+        .extend(crate::rules::SKIPPED.iter().map(|skipped| SkippedRule {
+            provider: skipped.provider.to_string(),
+            field: skipped.field.to_string(),
+            pattern: skipped.pattern.to_string(),
+            reason: skipped.reason.to_string(),
+            source: RuleSource::BuiltIn.to_string(),
+        }));
+
+    set.info = RulesetInfo {
+        source: RuleSource::BuiltIn.to_string(),
+        sha256: Some(BUILD_INFO.sha256.to_string()),
+        commit: BUILD_INFO.commit.map(str::to_string),
+        timestamp: Some(BUILD_INFO.built_at),
+        providers: BUILD_INFO.providers,
+        rules: BUILD_INFO.rules,
+        overlays: Vec::new(),
+    };
+
+    set
+});
+
+/// The ruleset installed with `plink rules update`, if there is one that loads
+#[cfg(not(target_arch = "wasm32"))]
+static CACHED: LazyLock<Option<RuleSet>> = LazyLock::new(|| {
+    let cache = crate::update::RulesCache::open_default()?;
+    match cache.load_current() {
+        Ok(rules) => rules,
+        Err(e) => {
+            warn!(
+                "Ignoring the cached rules in {}: {:#}",
+                cache.dir().display(),
+                e
+            );
+            None
+        }
+    }
+});
+
+/// Where a provider or a single rule was defined
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum RuleSource {
+    /// The ClearURLs data embedded at build time
+    #[default]
+    BuiltIn,
+    /// A user supplied rule file, labelled by its path
+    User(Arc<str>),
+}
+
+impl fmt::Display for RuleSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleSource::BuiltIn => write!(f, "built-in"),
+            RuleSource::User(label) => write!(f, "{}", label),
+        }
+    }
+}
+
+/// A compiled pattern, along with where it came from
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub pattern: Pattern,
+    pub source: RuleSource,
+}
+
+impl Rule {
+    /// Translate and compile a pattern from a rule file
+    pub fn new(pattern: &str, usage: Usage, source: &RuleSource) -> Result<Self> {
+        let pattern =
+            Pattern::new(pattern, usage).with_context(|| format!("Invalid rule in {}", source))?;
+
+        Ok(Self {
+            pattern,
+            source: source.clone(),
+        })
+    }
+
+    /// The pattern as written in the rule file
+    pub fn as_str(&self) -> &str {
+        self.pattern.as_str()
+    }
+
+    pub fn is_match(&self, haystack: &str) -> bool {
+        self.pattern.is_match(haystack)
+    }
+}
+
+/// A parameter rule that only fires for some values, like `ref` when it's an affiliate ID
+#[derive(Debug, Clone)]
+pub struct ValueRule {
+    /// Matched against the whole key, like the other parameter rules
+    pub key: Rule,
+    /// Matched against the whole decoded value, case sensitively
+    pub value: Rule,
+}
+
+impl ValueRule {
+    pub fn is_match(&self, key: &str, value: &str) -> bool {
+        self.key.is_match(key) && self.value.is_match(value)
+    }
+
+    /// The rule as `key=value`, the way it's reported
+    pub fn pattern(&self) -> String {
+        format!("{}={}", self.key.as_str(), self.value.as_str())
+    }
+}
+
+/// A pattern that couldn't be compiled by any available engine, and was left out of the ruleset
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkippedRule {
+    pub provider: String,
+    /// Which list the pattern was in, e.g. `rules` or `urlPattern`
+    pub field: String,
+    pub pattern: String,
+    pub reason: String,
+    /// `built-in` or the rule file it came from
+    pub source: String,
+}
+
+/// A provider defines cleaning rules for specific domains/services
+#[derive(Debug, Clone)]
+pub struct Provider {
+    pub name: String,
+    /// Who defined the provider (and its urlPattern)
+    pub source: RuleSource,
+    pub url_pattern: Rule,
+    pub rules: Vec<Rule>,
+    pub value_rules: Vec<ValueRule>,
+    pub raw_rules: Vec<Rule>,
+    pub exceptions: Vec<Rule>,
+    pub redirections: Vec<Rule>,
+    pub referral_marketing: Vec<Rule>,
+    pub complete_provider: bool,
+    pub force_redirection: bool,
+}
+
+impl Provider {
+    pub(crate) fn matches_url(&self, url: &Url) -> Result<bool> {
+        Ok(self.url_pattern.is_match(url.as_str()))
+    }
+
+    pub(crate) fn matches_exception(&self, url: &Url) -> Result<bool> {
+        for exception in self.exceptions.iter() {
+            if exception.is_match(url.as_str()) {
+                debug!("URL {} matches exception in provider {}", url, self.name);
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// How a provider in a rule file combines with an existing provider of the same name
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeMode {
+    /// Append the rules to the existing provider, overriding any flags that are set
+    #[default]
+    Extend,
+    /// Throw away the existing provider and use this one instead
+    Replace,
+}
+
+/// A provider as written in a rule file.
+///
+/// This is the ClearURLs schema, with a few additions to make hand written (TOML) files friendlier:
+/// `domains` can stand in for `urlPattern`, `params` takes literal parameter names instead of regexes and
+/// `valueRules` only remove a parameter when its value matches too.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProviderSpec {
+    #[serde(
+        rename = "urlPattern",
+        alias = "url_pattern",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub url_pattern: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub domains: Vec<String>,
+    pub rules: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub params: Vec<String>,
+    #[serde(
+        rename = "valueRules",
+        alias = "value_rules",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub value_rules: Vec<ValueRuleSpec>,
+    #[serde(rename = "rawRules", alias = "raw_rules")]
+    pub raw_rules: Vec<String>,
+    pub exceptions: Vec<String>,
+    pub redirections: Vec<String>,
+    #[serde(rename = "referralMarketing", alias = "referral_marketing")]
+    pub referral_marketing: Vec<String>,
+    #[serde(
+        rename = "completeProvider",
+        alias = "complete_provider",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub complete_provider: Option<bool>,
+    #[serde(
+        rename = "forceRedirection",
+        alias = "force_redirection",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub force_redirection: Option<bool>,
+    #[serde(skip_serializing_if = "is_default")]
+    pub merge: MergeMode,
+}
+
+/// A value rule as written in a rule file: a key regex and a value regex that both have to match
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValueRuleSpec {
+    pub key: String,
+    pub value: String,
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+impl ProviderSpec {
+    /// The explicit urlPattern, or one built from `domains` (matching them and their subdomains).
+    ///
+    /// A domain ending in `.*`, like `google.*`, matches any top level domain.
+    pub fn url_pattern(&self) -> Option<String> {
+        if let Some(pattern) = &self.url_pattern {
+            return Some(pattern.clone());
+        }
+
+        if self.domains.is_empty() {
+            return None;
+        }
+
+        let domains = self
+            .domains
+            .iter()
+            .map(|domain| {
+                let domain = domain.trim_start_matches("*.");
+                match domain.strip_suffix(".*") {
+                    Some(name) => format!(r"{}(?:\.[a-z]{{2,}}){{1,}}", regex::escape(name)),
+                    None => regex::escape(domain),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("|");
+
+        Some(format!(
+            r"^https?:\/\/(?:[a-z0-9-]+\.)*?(?:{})(?:[:/?#]|$)",
+            domains
+        ))
+    }
+
+    /// The parameter rules, with the literal `params` escaped into regexes
+    pub fn param_rules(&self) -> Vec<String> {
+        self.rules
+            .iter()
+            .cloned()
+            .chain(self.params.iter().map(|param| regex::escape(param)))
+            .collect()
+    }
+}
+
+/// A rule file, either a full ClearURLs dataset or a user overlay
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RuleFile {
+    pub providers: BTreeMap<String, ProviderSpec>,
+}
+
+impl RuleFile {
+    /// Parse a rule file in the ClearURLs JSON schema
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str(json).context("Failed to parse JSON rule file")
+    }
+
+    /// Parse a rule file written in TOML
+    pub fn from_toml(toml: &str) -> Result<Self> {
+        toml::from_str(toml).context("Failed to parse TOML rule file")
+    }
+
+    /// Read a rule file, picking the format from the file extension (JSON unless it ends in `.toml`)
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read rule file {}", path.display()))?;
+        Self::parse(&contents, path)
+    }
+
+    /// Parse the contents of a rule file, in the format its path implies
+    fn parse(contents: &str, path: &Path) -> Result<Self> {
+        let is_toml = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+
+        if is_toml {
+            Self::from_toml(contents)
+        } else {
+            Self::from_json(contents)
+        }
+        .with_context(|| format!("Invalid rule file {}", path.display()))
+    }
+}
+
+/// Which rules a `RuleSet` holds, so bug reports can say exactly what was active
+#[derive(Debug, Clone, Default, PartialEq, Tsify, Serialize, Deserialize)]
+pub struct RulesetInfo {
+    /// `built-in`, the cached version or the rule file the ruleset was loaded from
+    pub source: String,
+    /// SHA-256 of the ClearURLs JSON (or rule file) the ruleset was built from
+    pub sha256: Option<String>,
+    /// Commit of the ClearURLs rules submodule, for the embedded rules
+    pub commit: Option<String>,
+    /// When the rules were built into the binary or fetched into the cache, in seconds since the Unix epoch
+    pub timestamp: Option<u64>,
+    pub providers: usize,
+    /// Patterns across all providers, not counting their urlPatterns
+    pub rules: usize,
+    /// User rule files merged on top, in order
+    pub overlays: Vec<String>,
+}
+
+/// A compiled, ordered set of providers that a `UrlCleaner` runs over
+#[derive(Debug, Clone, Default)]
+pub struct RuleSet {
+    providers: Vec<Provider>,
+    skipped: Vec<SkippedRule>,
+    info: RulesetInfo,
+}
+
+impl RuleSet {
+    /// The ClearURLs rules embedded in the binary
+    pub fn embedded() -> &'static RuleSet {
+        &EMBEDDED
+    }
+
+    /// The ruleset installed with `plink rules update`, falling back to the embedded rules
+    pub fn active() -> &'static RuleSet {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(cached) = CACHED.as_ref() {
+            return cached;
+        }
+        &EMBEDDED
+    }
+
+    /// Build a ruleset from a single rule file, without any of the embedded rules
+    pub fn from_file(file: &RuleFile, source: RuleSource) -> Result<Self> {
+        let mut set = Self {
+            info: RulesetInfo {
+                source: source.to_string(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        set.merge(file, source)?;
+        Ok(set)
+    }
+
+    /// Load a rule file from disk as a standalone ruleset
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read rule file {}", path.display()))?;
+        let file = RuleFile::parse(&contents, path)?;
+
+        let mut set = Self::from_file(&file, RuleSource::User(path.display().to_string().into()))?;
+        set.info.sha256 = Some(sha256_hex(contents.as_bytes()));
+        Ok(set)
+    }
+
+    /// Where the ruleset came from and how big it is
+    pub fn info(&self) -> &RulesetInfo {
+        &self.info
+    }
+
+    /// Record the hash and timestamp of a ruleset that was loaded from somewhere other than a plain file
+    pub(crate) fn set_origin(&mut self, sha256: String, timestamp: u64) {
+        self.info.sha256 = Some(sha256);
+        self.info.timestamp = Some(timestamp);
+    }
+
+    /// All providers, in the order they're applied
+    pub fn providers(&self) -> &[Provider] {
+        &self.providers
+    }
+
+    /// Patterns that were left out because no available engine could compile them
+    pub fn skipped(&self) -> &[SkippedRule] {
+        &self.skipped
+    }
+
+    /// Look up a provider by name
+    pub fn provider(&self, name: &str) -> Option<&Provider> {
+        self.providers.iter().find(|provider| provider.name == name)
+    }
+
+    /// Turn the ruleset back into a rule file in the ClearURLs schema
+    pub fn to_file(&self) -> RuleFile {
+        let patterns = |rules: &[Rule]| -> Vec<String> {
+            rules.iter().map(|rule| rule.as_str().to_string()).collect()
+        };
+
+        let providers = self
+            .providers
+            .iter()
+            .map(|provider| {
+                let spec = ProviderSpec {
+                    url_pattern: Some(provider.url_pattern.as_str().to_string()),
+                    rules: patterns(&provider.rules),
+                    value_rules: provider
+                        .value_rules
+                        .iter()
+                        .map(|rule| ValueRuleSpec {
+                            key: rule.key.as_str().to_string(),
+                            value: rule.value.as_str().to_string(),
+                        })
+                        .collect(),
+                    raw_rules: patterns(&provider.raw_rules),
+                    exceptions: patterns(&provider.exceptions),
+                    redirections: patterns(&provider.redirections),
+                    referral_marketing: patterns(&provider.referral_marketing),
+                    complete_provider: Some(provider.complete_provider),
+                    force_redirection: Some(provider.force_redirection),
+                    ..Default::default()
+                };
+                (provider.name.clone(), spec)
+            })
+            .collect();
+
+        RuleFile { providers }
+    }
+
+    /// Load a rule file from disk and merge it on top of this ruleset
+    pub fn merge_path(&mut self, path: &Path) -> Result<()> {
+        let file = RuleFile::load(path)?;
+        self.merge(&file, RuleSource::User(path.display().to_string().into()))
+    }
+
+    /// Merge the providers of a rule file into this ruleset.
+    ///
+    /// Providers that don't exist yet are appended, existing ones are extended or replaced by name
+    /// according to their `merge` mode.
+    pub fn merge(&mut self, file: &RuleFile, source: RuleSource) -> Result<()> {
+        for (name, spec) in &file.providers {
+            let mut skipped = Vec::new();
+
+            // Unsupported patterns are dropped with a warning rather than failing the whole file
+            let mut compile = |field: &str, patterns: &[String], usage: Usage| -> Vec<Rule> {
+                patterns
+                    .iter()
+                    .filter_map(|pattern| match Rule::new(pattern, usage, &source) {
+                        Ok(rule) => Some(rule),
+                        Err(e) => {
+                            warn!("Skipping {} pattern of provider {}: {:#}", field, name, e);
+                            skipped.push(SkippedRule {
+                                provider: name.clone(),
+                                field: field.to_string(),
+                                pattern: pattern.clone(),
+                                reason: format!("{:#}", e),
+                                source: source.to_string(),
+                            });
+                            None
+                        }
+                    })
+                    .collect()
+            };
+
+            let url_pattern = match spec.url_pattern() {
+                Some(pattern) => {
+                    let compiled = compile("urlPattern", &[pattern], Usage::Url);
+                    if compiled.is_empty() {
+                        // Without its urlPattern the provider can't apply to anything
+                        self.skipped.append(&mut skipped);
+                        continue;
+                    }
+                    compiled.into_iter().next()
+                }
+                None => None,
+            };
+            let rules = compile("rules", &spec.param_rules(), Usage::Key);
+            let value_rules = spec
+                .value_rules
+                .iter()
+                .filter_map(|rule| {
+                    let key = compile("valueRules", slice::from_ref(&rule.key), Usage::Key);
+                    let value = compile("valueRules", slice::from_ref(&rule.value), Usage::Value);
+                    Some(ValueRule {
+                        key: key.into_iter().next()?,
+                        value: value.into_iter().next()?,
+                    })
+                })
+                .collect::<Vec<_>>();
+            let raw_rules = compile("rawRules", &spec.raw_rules, Usage::Replace);
+            let exceptions = compile("exceptions", &spec.exceptions, Usage::Url);
+            let redirections = compile("redirections", &spec.redirections, Usage::Url);
+            let referral_marketing =
+                compile("referralMarketing", &spec.referral_marketing, Usage::Key);
+            self.skipped.append(&mut skipped);
+
+            let existing = self.providers.iter_mut().find(|p| p.name == *name);
+
+            match existing {
+                Some(provider) if spec.merge == MergeMode::Extend => {
+                    debug!("Extending provider {} from {}", name, source);
+
+                    if let Some(url_pattern) = url_pattern {
+                        provider.url_pattern = url_pattern;
+                    }
+                    provider.rules.extend(rules);
+                    provider.value_rules.extend(value_rules);
+                    provider.raw_rules.extend(raw_rules);
+                    provider.exceptions.extend(exceptions);
+                    provider.redirections.extend(redirections);
+                    provider.referral_marketing.extend(referral_marketing);
+                    if let Some(complete_provider) = spec.complete_provider {
+                        provider.complete_provider = complete_provider;
+                    }
+                    if let Some(force_redirection) = spec.force_redirection {
+                        provider.force_redirection = force_redirection;
+                    }
+                }
+                existing => {
+                    let url_pattern = url_pattern.with_context(|| {
+                        format!("Provider '{}' needs a urlPattern or domains", name)
+                    })?;
+
+                    let provider = Provider {
+                        name: name.clone(),
+                        source: source.clone(),
+                        url_pattern,
+                        rules,
+                        value_rules,
+                        raw_rules,
+                        exceptions,
+                        redirections,
+                        referral_marketing,
+                        complete_provider: spec.complete_provider.unwrap_or(false),
+                        force_redirection: spec.force_redirection.unwrap_or(false),
+                    };
+
+                    match existing {
+                        Some(slot) => {
+                            debug!("Replacing provider {} from {}", name, source);
+                            *slot = provider;
+                        }
+                        None => self.providers.push(provider),
+                    }
+                }
+            }
+        }
+
+        info!("Merged {} providers from {}", file.providers.len(), source);
+
+        let label = source.to_string();
+        if source != RuleSource::BuiltIn && label != self.info.source {
+            self.info.overlays.push(label);
+        }
+        self.info.providers = self.providers.len();
+        self.info.rules = self
+            .providers
+            .iter()
+            .map(|provider| {
+                provider.rules.len()
+                    + provider.value_rules.len()
+                    + provider.raw_rules.len()
+                    + provider.exceptions.len()
+                    + provider.redirections.len()
+                    + provider.referral_marketing.len()
+            })
+            .sum();
+
+        Ok(())
+    }
+}
+
+/// Hex encoded SHA-256 of a ruleset
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toml_overlay() {
+        let file = RuleFile::from_toml(
+            r#"
+            [providers.intranet]
+            domains = ["corp.example"]
+            params = ["trk.id"]
+            "#,
+        )
+        .unwrap();
+
+        let source = RuleSource::User("corp.toml".into());
+        let set = RuleSet::from_file(&file, source.clone()).unwrap();
+        let provider = set.provider("intranet").unwrap();
+
+        assert_eq!(provider.source, source);
+        assert_eq!(provider.rules[0].as_str(), r"trk\.id");
+        assert!(
+            provider
+                .matches_url(&Url::parse("https://wiki.corp.example/page").unwrap())
+                .unwrap()
+        );
+        assert!(
+            !provider
+                .matches_url(&Url::parse("https://corp.example.org/").unwrap())
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_merge_modes() {
+        let mut set = RuleSet::default();
+        let base = RuleFile::from_json(
+            r#"{"providers": {"shop": {"urlPattern": "shop\\.test", "rules": ["a"]}}}"#,
+        )
+        .unwrap();
+        set.merge(&base, RuleSource::BuiltIn).unwrap();
+
+        let extend = RuleFile::from_json(r#"{"providers": {"shop": {"rules": ["b"]}}}"#).unwrap();
+        set.merge(&extend, RuleSource::User("extend.json".into()))
+            .unwrap();
+        let rules = &set.provider("shop").unwrap().rules;
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[1].source, RuleSource::User("extend.json".into()));
+
+        let replace = RuleFile::from_json(
+            r#"{"providers": {"shop": {"urlPattern": "shop\\.test", "rules": ["c"], "merge": "replace"}}}"#,
+        )
+        .unwrap();
+        set.merge(&replace, RuleSource::User("replace.json".into()))
+            .unwrap();
+        let rules = &set.provider("shop").unwrap().rules;
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].as_str(), "c");
+
+        assert_eq!(set.info().rules, 1);
+        assert_eq!(set.info().overlays, vec!["extend.json", "replace.json"]);
+    }
+
+    #[test]
+    fn test_embedded_ruleset_info() {
+        let info = RuleSet::embedded().info();
+        assert_eq!(info.source, "built-in");
+        assert_eq!(info.sha256.as_ref().map(String::len), Some(64));
+        assert_eq!(info.providers, RuleSet::embedded().providers().len());
+        assert!(info.timestamp.is_some());
+    }
+
+    #[test]
+    fn test_unsupported_patterns_are_skipped() {
+        let file = RuleFile::from_json(
+            r#"{"providers": {"shop": {"urlPattern": "shop\\.test", "rules": ["(?<!x)ref", "(unclosed", "\\/?id"]}}}"#,
+        )
+        .unwrap();
+        let set = RuleSet::from_file(&file, RuleSource::BuiltIn).unwrap();
+
+        let skipped: Vec<&str> = set.skipped().iter().map(|s| s.pattern.as_str()).collect();
+        assert!(skipped.contains(&"(unclosed"));
+        assert_eq!(
+            skipped.contains(&"(?<!x)ref"),
+            !cfg!(feature = "backtracking")
+        );
+
+        let rules = &set.provider("shop").unwrap().rules;
+        assert!(rules.iter().any(|rule| rule.is_match("ID")));
+    }
+}
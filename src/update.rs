@@ -0,0 +1,444 @@
+//! Fetching newer ClearURLs rulesets at runtime, and the versioned cache they're kept in
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result, anyhow, bail};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::{RuleFile, RuleSet, RuleSource, lint, ruleset::sha256_hex};
+
+/// Overrides where the rules cache lives
+pub const CACHE_DIR_ENV: &str = "PLINK_RULES_DIR";
+
+/// Name of the ruleset inside each version directory
+const DATA_FILE: &str = "data.min.json";
+
+/// Downloaded rulesets are never expected to come anywhere near this
+const MAX_DOWNLOAD_SIZE: u64 = 32 * 1024 * 1024;
+
+/// A ruleset stored in the cache
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedVersion {
+    /// Directory name of the version, `<unix time>-<hash prefix>`
+    pub id: String,
+    /// Where it was fetched from
+    pub source: String,
+    /// SHA-256 of the ruleset, hex encoded
+    pub sha256: String,
+    /// When it was fetched, in seconds since the Unix epoch
+    pub fetched_at: u64,
+    pub providers: usize,
+}
+
+/// Which cached versions are in use, stored as `state.json`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CacheState {
+    current: Option<CachedVersion>,
+    previous: Option<CachedVersion>,
+    /// Every version directory this cache created and hasn't removed yet, so pruning never touches anything
+    /// else in the directory
+    #[serde(default)]
+    installed: Vec<String>,
+}
+
+/// Rulesets fetched with `plink rules update`, keeping the current version and the one before it.
+///
+/// Each version lives in its own directory next to a `state.json` that records which one is active.
+#[derive(Debug, Clone)]
+pub struct RulesCache {
+    dir: PathBuf,
+}
+
+/// What an update did
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpdateReport {
+    pub installed: CachedVersion,
+    pub previous: Option<CachedVersion>,
+    /// The fetched ruleset was identical to the current one, so nothing changed
+    pub unchanged: bool,
+    /// Lint errors that were overridden with `force`
+    pub lint_errors: Vec<String>,
+}
+
+impl RulesCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// The cache in `$PLINK_RULES_DIR`, or the user's cache directory
+    pub fn open_default() -> Option<Self> {
+        match env::var_os(CACHE_DIR_ENV) {
+            Some(dir) => Some(Self::new(dir)),
+            None => dirs::cache_dir().map(|dir| Self::new(dir.join("plink").join("rules"))),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The active cached version, if there is one
+    pub fn current(&self) -> Result<Option<CachedVersion>> {
+        Ok(self.state()?.current)
+    }
+
+    /// The version a rollback would go back to
+    pub fn previous(&self) -> Result<Option<CachedVersion>> {
+        Ok(self.state()?.previous)
+    }
+
+    /// Compile the active cached version
+    pub fn load_current(&self) -> Result<Option<RuleSet>> {
+        let Some(current) = self.current()? else {
+            return Ok(None);
+        };
+
+        let path = self.dir.join(&current.id).join(DATA_FILE);
+        let file = RuleFile::load(&path)?;
+        let mut rules = RuleSet::from_file(&file, RuleSource::User(current.label().into()))?;
+        rules.set_origin(current.sha256, current.fetched_at);
+        Ok(Some(rules))
+    }
+
+    /// Store an already verified ruleset and make it the current version
+    pub fn install(&self, data: &[u8], source: &str) -> Result<(CachedVersion, bool)> {
+        let mut state = self.state()?;
+        let sha256 = sha256_hex(data);
+
+        if let Some(current) = &state.current
+            && current.sha256 == sha256
+        {
+            return Ok((current.clone(), false));
+        }
+
+        let file = RuleFile::from_json(std::str::from_utf8(data).context("Ruleset is not UTF-8")?)?;
+        let fetched_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let version = CachedVersion {
+            id: format!("{}-{}", fetched_at, &sha256[..12]),
+            source: source.to_string(),
+            sha256,
+            fetched_at,
+            providers: file.providers.len(),
+        };
+
+        let version_dir = self.dir.join(&version.id);
+        fs::create_dir_all(&version_dir)
+            .with_context(|| format!("Failed to create {}", version_dir.display()))?;
+        fs::write(version_dir.join(DATA_FILE), data)
+            .with_context(|| format!("Failed to write to {}", version_dir.display()))?;
+
+        // States written before `installed` existed still name the versions they created
+        for id in [&state.current, &state.previous]
+            .into_iter()
+            .flatten()
+            .map(|v| &v.id)
+        {
+            if !state.installed.contains(id) {
+                state.installed.push(id.clone());
+            }
+        }
+
+        state.previous = state.current.take();
+        state.current = Some(version.clone());
+        state.installed.push(version.id.clone());
+        self.prune(&mut state);
+        self.save_state(&state)?;
+
+        info!("Installed rules {} from {}", version.id, source);
+        Ok((version, true))
+    }
+
+    /// Swap the current and previous versions, returning the one that's now current
+    pub fn rollback(&self) -> Result<CachedVersion> {
+        let mut state = self.state()?;
+        let previous = state
+            .previous
+            .take()
+            .ok_or_else(|| anyhow!("There is no previous ruleset to roll back to"))?;
+
+        state.previous = state.current.take();
+        state.current = Some(previous.clone());
+        self.save_state(&state)?;
+
+        info!("Rolled back to rules {}", previous.id);
+        Ok(previous)
+    }
+
+    fn state(&self) -> Result<CacheState> {
+        let path = self.dir.join("state.json");
+        if !path.exists() {
+            return Ok(CacheState::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&contents).with_context(|| format!("Invalid {}", path.display()))
+    }
+
+    fn save_state(&self, state: &CacheState) -> Result<()> {
+        let path = self.dir.join("state.json");
+        let tmp = self.dir.join("state.json.tmp");
+
+        // Write then rename, so a crash never leaves a half written state behind
+        fs::write(&tmp, serde_json::to_string_pretty(state)?)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &path).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Remove the installed versions that are neither current nor previous
+    fn prune(&self, state: &mut CacheState) {
+        let keep: Vec<String> = [&state.current, &state.previous]
+            .into_iter()
+            .flatten()
+            .map(|version| version.id.clone())
+            .collect();
+
+        state.installed.retain(|id| {
+            if keep.contains(id) {
+                return true;
+            }
+
+            let path = self.dir.join(id);
+            debug!("Pruning old rules {}", path.display());
+            match fs::remove_dir_all(&path) {
+                Ok(()) => false,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+                Err(e) => {
+                    // Kept, so the next install tries again
+                    warn!("Failed to remove {}: {}", path.display(), e);
+                    true
+                }
+            }
+        });
+    }
+}
+
+impl CachedVersion {
+    /// How rules from this version are labelled in `RuleMatch::source`
+    pub fn label(&self) -> String {
+        format!("cache {}", self.id)
+    }
+}
+
+/// Read a file path, `file://` URL or `http(s)://` URL
+pub fn fetch(location: &str) -> Result<Vec<u8>> {
+    let url = Url::parse(location).ok();
+
+    match url.as_ref().map(Url::scheme) {
+        Some("file") => {
+            let path = url
+                .as_ref()
+                .and_then(|url| url.to_file_path().ok())
+                .ok_or_else(|| anyhow!("Invalid file URL {}", location))?;
+            fs::read(&path).with_context(|| format!("Failed to read {}", path.display()))
+        }
+        Some("http" | "https") => {
+            debug!("Downloading {}", location);
+            let mut response = ureq::get(location)
+                .call()
+                .with_context(|| format!("Failed to download {}", location))?;
+            response
+                .body_mut()
+                .with_config()
+                .limit(MAX_DOWNLOAD_SIZE)
+                .read_to_vec()
+                .with_context(|| format!("Failed to download {}", location))
+        }
+        // Anything else is a local path (which includes Windows drive letters that parse as a scheme)
+        _ => fs::read(location).with_context(|| format!("Failed to read {}", location)),
+    }
+}
+
+/// Where the hash of a ruleset is published, by ClearURLs' naming or else as a `.sha256` sibling
+pub fn default_hash_location(location: &str) -> String {
+    match location.strip_suffix("data.minify.json") {
+        Some(base) => format!("{}rules.minify.hash", base),
+        None => format!("{}.sha256", location),
+    }
+}
+
+/// Check `data` against a hash file, which holds the hex digest optionally followed by a file name
+pub fn verify_hash(data: &[u8], hash_file: &[u8]) -> Result<()> {
+    let hash_file = String::from_utf8_lossy(hash_file);
+    let expected = hash_file
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| anyhow!("The hash file is empty"))?;
+
+    if expected.len() != 64 || !expected.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("The hash file doesn't contain a SHA-256 digest");
+    }
+
+    let actual = sha256_hex(data);
+    if !actual.eq_ignore_ascii_case(expected) {
+        bail!(
+            "Hash mismatch: expected {}, got {}",
+            expected.to_ascii_lowercase(),
+            actual
+        );
+    }
+
+    Ok(())
+}
+
+/// Fetch a ruleset, verify its hash, check that every pattern compiles and install it into the cache.
+///
+/// Lint errors stop the update unless `force` is set, in which case they're returned in the report.
+pub fn update(
+    cache: &RulesCache,
+    from: &str,
+    hash: Option<&str>,
+    force: bool,
+) -> Result<UpdateReport> {
+    let data = fetch(from)?;
+
+    let hash_location = hash.map_or_else(|| default_hash_location(from), str::to_string);
+    let hash_file = fetch(&hash_location)?;
+    verify_hash(&data, &hash_file).with_context(|| format!("Failed to verify {}", from))?;
+
+    let json = std::str::from_utf8(&data).context("Ruleset is not UTF-8")?;
+    let report = lint::lint_json(json)?;
+    let lint_errors: Vec<String> = report
+        .issues
+        .iter()
+        .filter(|issue| issue.severity == lint::Severity::Error)
+        .map(|issue| issue.to_string())
+        .collect();
+
+    if !lint_errors.is_empty() && !force {
+        bail!(
+            "The ruleset has {} lint error(s), the first being: {}",
+            lint_errors.len(),
+            lint_errors[0]
+        );
+    }
+
+    let previous = cache.current()?;
+    let (installed, changed) = cache.install(&data, from)?;
+
+    Ok(UpdateReport {
+        installed,
+        previous: if changed { previous } else { cache.previous()? },
+        unchanged: !changed,
+        lint_errors,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        net::TcpListener,
+        thread,
+    };
+
+    use super::*;
+
+    const RULES: &str =
+        r#"{"providers": {"shop": {"urlPattern": "^https?:\\/\\/shop\\.test", "rules": ["ref"]}}}"#;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("plink-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_update_and_rollback() {
+        let dir = scratch_dir("update");
+        let cache = RulesCache::new(dir.join("cache"));
+
+        let first = dir.join("first.json");
+        fs::write(&first, RULES).unwrap();
+        fs::write(dir.join("first.json.sha256"), sha256_hex(RULES.as_bytes())).unwrap();
+
+        let second_rules = RULES.replace("\"ref\"", "\"ref\", \"tag\"");
+        let second = dir.join("second.json");
+        fs::write(&second, &second_rules).unwrap();
+        fs::write(dir.join("bad.sha256"), sha256_hex(RULES.as_bytes())).unwrap();
+
+        // A hash that doesn't match never reaches the cache
+        let bad_hash = dir.join("bad.sha256");
+        assert!(update(&cache, second.to_str().unwrap(), bad_hash.to_str(), false).is_err());
+        assert_eq!(cache.current().unwrap(), None);
+
+        let file_url = Url::from_file_path(&first).unwrap().to_string();
+        let report = update(&cache, &file_url, None, false).unwrap();
+        assert_eq!(report.installed.providers, 1);
+        assert!(report.previous.is_none());
+
+        fs::write(
+            dir.join("second.json.sha256"),
+            sha256_hex(second_rules.as_bytes()),
+        )
+        .unwrap();
+        let report = update(&cache, second.to_str().unwrap(), None, false).unwrap();
+        assert_eq!(
+            report.previous.as_ref().unwrap().sha256,
+            sha256_hex(RULES.as_bytes())
+        );
+
+        let rules = cache.load_current().unwrap().unwrap();
+        assert_eq!(rules.provider("shop").unwrap().rules.len(), 2);
+
+        let restored = cache.rollback().unwrap();
+        assert_eq!(restored.sha256, sha256_hex(RULES.as_bytes()));
+        let rules = cache.load_current().unwrap().unwrap();
+        assert_eq!(rules.provider("shop").unwrap().rules.len(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_prune_keeps_foreign_directories() {
+        let dir = scratch_dir("prune");
+        let cache = RulesCache::new(&dir);
+        fs::create_dir_all(dir.join("backup")).unwrap();
+
+        let mut ids = Vec::new();
+        for rule in ["ref", "tag", "src"] {
+            let rules = RULES.replace("\"ref\"", &format!("\"{}\"", rule));
+            ids.push(cache.install(rules.as_bytes(), "test").unwrap().0.id);
+        }
+
+        assert!(dir.join("backup").is_dir());
+        assert!(!dir.join(&ids[0]).exists());
+        assert!(dir.join(&ids[1]).is_dir() && dir.join(&ids[2]).is_dir());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_fetch_over_http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let _ = stream.read(&mut request).unwrap();
+            write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                RULES.len(),
+                RULES
+            )
+            .unwrap();
+        });
+
+        let data = fetch(&format!("http://{}/data.minify.json", address)).unwrap();
+        assert_eq!(data, RULES.as_bytes());
+        server.join().unwrap();
+    }
+}
@@ -1,6 +1,6 @@
 use wasm_bindgen::prelude::*;
 
-use crate::{CleaningOptions, CleaningResult, UrlCleaner};
+use crate::{CleaningOptions, CleaningResult, RuleSet, UrlCleaner, export::dnr};
 
 #[wasm_bindgen]
 pub fn clean_url(url: &str, options: JsValue) -> Result<JsValue, JsValue> {
@@ -9,19 +9,10 @@ pub fn clean_url(url: &str, options: JsValue) -> Result<JsValue, JsValue> {
 
     let cleaner = UrlCleaner::new(options).map_err(|e| JsValue::from_str(&e.to_string()))?;
 
-    let result = cleaner
+    let result: CleaningResult = cleaner
         .clean_url(url)
         .map_err(|e| JsValue::from_str(&e.to_string()))?;
 
-    // Convert Url to String
-    let result = CleaningResult {
-        url: result.url.to_string(),
-        changed: result.changed,
-        redirect: result.redirect,
-        cancel: result.cancel,
-        applied_rules: result.applied_rules,
-    };
-
     serde_wasm_bindgen::to_value(&result).map_err(|e| JsValue::from_str(&e.to_string()))
 }
 
@@ -41,3 +32,20 @@ pub fn clean_url_simple(url: &str) -> Result<String, JsValue> {
 pub fn default_options() -> JsValue {
     serde_wasm_bindgen::to_value(&CleaningOptions::default()).unwrap()
 }
+
+#[wasm_bindgen]
+pub fn ruleset_info() -> Result<JsValue, JsValue> {
+    serde_wasm_bindgen::to_value(&crate::ruleset_info())
+        .map_err(|e| JsValue::from_str(&e.to_string()))
+}
+
+#[wasm_bindgen]
+pub fn export_dnr(options: JsValue) -> Result<JsValue, JsValue> {
+    let options: CleaningOptions =
+        serde_wasm_bindgen::from_value(options).map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    let export = dnr::export(RuleSet::active(), &options, &dnr::Limits::default())
+        .map_err(|e| JsValue::from_str(&e.to_string()))?;
+
+    serde_wasm_bindgen::to_value(&export).map_err(|e| JsValue::from_str(&e.to_string()))
+}